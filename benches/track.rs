@@ -0,0 +1,51 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use rust_rocket::interpolation::Interpolation;
+use rust_rocket::track::{Key, Track, TrackCursor};
+
+const ROWS_PER_KEY: u32 = 4;
+
+fn large_track(keys: u32) -> Track {
+    let mut track = Track::new("bench");
+    for i in 0..keys {
+        let interpolation = Interpolation::from((i % 4) as u8);
+        track.set_key(Key::new(i * ROWS_PER_KEY, i as f32, interpolation));
+    }
+    track
+}
+
+fn get_value(c: &mut Criterion) {
+    let mut group = c.benchmark_group("get_value");
+    for keys in [16, 1024, 16384] {
+        let track = large_track(keys);
+        let rows: Vec<f32> = (0..keys * ROWS_PER_KEY * 2)
+            .map(|row| row as f32 / 2.)
+            .collect();
+
+        group.bench_with_input(BenchmarkId::new("random_access", keys), &rows, |b, rows| {
+            b.iter(|| {
+                for &row in rows {
+                    black_box(track.get_value(black_box(row)));
+                }
+            })
+        });
+
+        group.bench_with_input(BenchmarkId::new("cursor", keys), &rows, |b, rows| {
+            b.iter(|| {
+                let mut cursor = TrackCursor::new();
+                for &row in rows {
+                    black_box(cursor.get_value(&track, black_box(row)));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn set_key(c: &mut Criterion) {
+    c.bench_function("set_key 16384", |b| {
+        b.iter(|| large_track(black_box(16384)))
+    });
+}
+
+criterion_group!(benches, get_value, set_key);
+criterion_main!(benches);
@@ -1,9 +1,12 @@
 use rust_rocket::client::{Event, RocketClient};
+use rust_rocket::file::{self, Metadata};
 use std::error::Error;
-use std::fs::OpenOptions;
+use std::fs::File;
 use std::time::Duration;
 
 static TRACKS_FILE: &str = "tracks.bin";
+const BPM: f32 = 120.;
+const ROWS_PER_BEAT: u32 = 8;
 
 fn main() -> Result<(), Box<dyn Error>> {
     let mut rocket = RocketClient::new()?;
@@ -35,20 +38,15 @@ fn main() -> Result<(), Box<dyn Error>> {
                     // Obtain a clone of current track state
                     let tracks = rocket.save_tracks();
 
-                    // Open a file for writing, create if not present,
-                    // truncate (overwrite) in case it has previous contents.
-                    let mut file = OpenOptions::new()
-                        .write(true)
-                        .create(true)
-                        .truncate(true)
-                        .open(TRACKS_FILE)?;
-
-                    // Serialize tracks into the file using bincode
-                    let bincode_conf = bincode::config::standard();
-                    bincode::encode_into_std_write(tracks, &mut file, bincode_conf)?;
-                    // See examples/play.rs for deserializing and playback
+                    // Create the file, or truncate (overwrite) it in case it has previous contents
+                    let file = File::create(TRACKS_FILE)?;
+
+                    // Write tracks into the file, with the tempo of the production
+                    file::write(file, tracks, Metadata::new(BPM, ROWS_PER_BEAT))?;
+                    // See examples/play.rs for loading and playback
                     println!("Tracks saved to {}", TRACKS_FILE);
                 }
+                Event::Reconnected | Event::KeySet { .. } | Event::KeyDeleted { .. } => {}
             }
             println!("{:?}", event);
         }
@@ -0,0 +1,19 @@
+use rust_rocket::{file, xml};
+use std::error::Error;
+use std::fs::File;
+
+static TRACKS_FILE: &str = "tracks.bin";
+static PROJECT_FILE: &str = "tracks.rocket";
+
+fn main() -> Result<(), Box<dyn Error>> {
+    // Open previously saved file (see examples/edit.rs)
+    let file = File::open(TRACKS_FILE)?;
+    let tracks = file::read(file)?.into_tracks();
+
+    // Write the tracks as a project that the GNU Rocket editor can open
+    let file = File::create(PROJECT_FILE)?;
+    xml::write(file, &tracks)?;
+    println!("{} tracks exported to {}", tracks.len(), PROJECT_FILE);
+
+    Ok(())
+}
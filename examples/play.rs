@@ -8,12 +8,9 @@ static TRACKS_FILE: &str = "tracks.bin";
 fn main() -> Result<(), Box<dyn Error>> {
     let rocket = {
         // Open previously saved file (see examples/edit.rs)
-        let mut file = File::open(TRACKS_FILE)?;
-        // Deserialize from the file into Vec<Track> using bincode
-        let bincode_conf = bincode::config::standard();
-        let tracks = bincode::decode_from_std_read(&mut file, bincode_conf)?;
-        // Construct a new read-only, offline RocketPlayer
-        RocketPlayer::new(tracks)
+        let file = File::open(TRACKS_FILE)?;
+        // Read the tracks and construct a new read-only, offline RocketPlayer
+        RocketPlayer::load(file)?
     };
     println!("Tracks loaded from {}", TRACKS_FILE);
 
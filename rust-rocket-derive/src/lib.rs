@@ -0,0 +1,103 @@
+//! Derive macro for [rust-rocket](https://crates.io/crates/rust-rocket).
+//!
+//! Don't depend on this crate directly, enable the `derive` feature of `rust-rocket` instead.
+//! See `rust_rocket::RocketTracks` for documentation.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, LitStr};
+
+/// Derive `rust_rocket::RocketTracks` for a struct with named fields.
+///
+/// Every field becomes a track named `prefix:field`, or `field` if the struct has no `#[rocket(prefix = "...")]`
+/// attribute. Field types must implement `From<f32>`.
+#[proc_macro_derive(RocketTracks, attributes(rocket))]
+pub fn derive_rocket_tracks(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let prefix = parse_prefix(&input)?;
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "RocketTracks can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "RocketTracks can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut idents = Vec::new();
+    let mut names = Vec::new();
+    for field in fields {
+        for attr in &field.attrs {
+            if attr.path().is_ident("rocket") {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    "#[rocket(...)] is only supported on the struct",
+                ));
+            }
+        }
+        // Named fields always have an ident
+        let ident = field.ident.as_ref().unwrap();
+        let field_name = ident.to_string();
+        let field_name = field_name.strip_prefix("r#").unwrap_or(&field_name);
+        let name = match &prefix {
+            Some(prefix) => format!("{}:{}", prefix, field_name),
+            None => String::from(field_name),
+        };
+        idents.push(ident);
+        names.push(LitStr::new(&name, ident.span()));
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::rust_rocket::RocketTracks for #ident #ty_generics #where_clause {
+            const TRACK_NAMES: &'static [&'static str] = &[#(#names),*];
+
+            fn sample<S>(source: &S, row: f32) -> Self
+            where
+                S: ::rust_rocket::source::TrackSource + ?Sized,
+            {
+                Self {
+                    #(#idents: ::core::convert::From::from(source.get_value(#names, row)),)*
+                }
+            }
+        }
+    })
+}
+
+/// Parse `#[rocket(prefix = "...")]` from the struct attributes.
+fn parse_prefix(input: &DeriveInput) -> syn::Result<Option<String>> {
+    let mut prefix = None;
+    for attr in &input.attrs {
+        if !attr.path().is_ident("rocket") {
+            continue;
+        }
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("prefix") {
+                let value: LitStr = meta.value()?.parse()?;
+                prefix = Some(value.value());
+                Ok(())
+            } else {
+                Err(meta.error("unsupported rocket attribute, expected `prefix`"))
+            }
+        })?;
+    }
+    Ok(prefix)
+}
@@ -0,0 +1,368 @@
+//! Asynchronous client code, including the [`AsyncRocketClient`] type.
+//!
+//! Requires the `tokio` feature.
+//! The async client has the same track state semantics as [`RocketClient`](crate::RocketClient),
+//! but instead of polling a non-blocking socket, events are awaited or consumed as a [`Stream`].
+//!
+//! # Usage
+//!
+//! ```rust,no_run
+//! # use rust_rocket::async_client::AsyncRocketClient;
+//! # use rust_rocket::client::{Error, Event};
+//! # async fn run() -> Result<(), Error> {
+//! let mut rocket = AsyncRocketClient::new().await?;
+//! rocket.get_track_mut("camera:x").await?;
+//!
+//! loop {
+//!     match rocket.next_event().await? {
+//!         Event::SetRow(row) => {
+//!             let value = rocket.get_track("camera:x").unwrap().get_value(row as f32);
+//!             println!("camera:x = {} at row {}", value, row);
+//!         }
+//!         Event::Pause(_) => {}
+//!         Event::SaveTracks => {}
+//!         Event::Reconnected => {}
+//!         Event::KeySet { .. } | Event::KeyDeleted { .. } => {}
+//!     }
+//! }
+//! # }
+//! ```
+
+use crate::client::{Error, Event, TrackState, RECEIVE_BUFFER_LEN};
+use crate::protocol::{Decoder, Handshake, CLIENT_GREETING, SERVER_GREETING};
+use crate::track::{Track, TrackId};
+use crate::Tracks;
+
+use futures_core::Stream;
+use std::{
+    future::Future,
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf},
+    net::{TcpStream, ToSocketAddrs},
+};
+
+/// The `AsyncRocketClient` type. This contains the connected socket and other fields.
+///
+/// The stream type `S` is [`TcpStream`] unless the client is constructed with
+/// [`AsyncRocketClient::from_stream`].
+#[derive(Debug)]
+pub struct AsyncRocketClient<S = TcpStream> {
+    stream: S,
+    decoder: Decoder,
+    state: TrackState,
+    receive_buf: Box<[u8]>,
+}
+
+impl AsyncRocketClient {
+    /// Construct a new AsyncRocketClient.
+    ///
+    /// This constructs a new Rocket client and connects to localhost on port 1338.
+    ///
+    /// # Errors
+    ///
+    /// [`Error::Connect`] if connection cannot be established, or [`Error::Handshake`]
+    /// if the handshake fails.
+    pub async fn new() -> Result<Self, Error> {
+        Self::connect(("localhost", 1338)).await
+    }
+
+    /// Construct a new AsyncRocketClient.
+    ///
+    /// This constructs a new Rocket client and connects to a specified host and port.
+    ///
+    /// # Errors
+    ///
+    /// [`Error::Connect`] if connection cannot be established, or [`Error::Handshake`]
+    /// if the handshake fails.
+    pub async fn connect(addr: impl ToSocketAddrs) -> Result<Self, Error> {
+        let stream = TcpStream::connect(addr).await.map_err(Error::Connect)?;
+        Self::from_stream(stream).await
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncRocketClient<S> {
+    /// Construct a new AsyncRocketClient from a stream that is already connected to a tracker.
+    ///
+    /// # Errors
+    ///
+    /// [`Error::Handshake`] if the handshake fails.
+    pub async fn from_stream(stream: S) -> Result<Self, Error> {
+        let mut rocket = Self {
+            stream,
+            decoder: Decoder::new(),
+            state: TrackState::default(),
+            receive_buf: vec![0; RECEIVE_BUFFER_LEN].into_boxed_slice(),
+        };
+
+        rocket.handshake().await?;
+
+        Ok(rocket)
+    }
+
+    /// Get a reference to the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Get a mutable reference to the underlying stream.
+    ///
+    /// Reading from or writing to the stream directly will corrupt the connection.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    /// Get track by name.
+    ///
+    /// If the track does not yet exist it will be created.
+    /// Not cancel safe, see [`get_track_id`](Self::get_track_id).
+    ///
+    /// # Errors
+    ///
+    /// This method can return an [`Error::IOError`] if Rocket tracker disconnects.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `name`'s length exceeds [`u32::MAX`].
+    pub async fn get_track_mut(&mut self, name: &str) -> Result<&mut Track, Error> {
+        let id = self.get_track_id(name).await?;
+        Ok(self.state.track_mut(id.index()))
+    }
+
+    /// Get a handle to a track by name, creating the track if it does not yet exist.
+    ///
+    /// See [`RocketClient::get_track_id`](crate::RocketClient::get_track_id).
+    ///
+    /// # Cancel safety
+    ///
+    /// This method is not cancel safe. If the future is dropped while the request is being sent, the track isn't
+    /// created, but part of the request may have been sent, which corrupts the connection. Drop the client then.
+    ///
+    /// # Errors
+    ///
+    /// This method can return an [`Error::IOError`] if Rocket tracker disconnects.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `name`'s length exceeds [`u32::MAX`].
+    pub async fn get_track_id(&mut self, name: &str) -> Result<TrackId, Error> {
+        if let Some(id) = self.state.existing_track_id(name) {
+            Ok(id)
+        } else {
+            // Send GET_TRACK message, and only add the track once the tracker will assign it an index
+            let mut buf = Vec::new();
+            crate::protocol::encode_get_track(&mut buf, name);
+            self.stream.write_all(&buf).await.map_err(Error::IOError)?;
+            Ok(self.state.push(name))
+        }
+    }
+
+    /// Get track by a handle from [`get_track_id`](Self::get_track_id).
+    pub fn get_track_by_id(&self, id: TrackId) -> Option<&Track> {
+        self.state.get_track_by_id(id)
+    }
+
+    /// Get track by name.
+    ///
+    /// You should use [`get_track_mut`](Self::get_track_mut) to create a track.
+    pub fn get_track(&self, name: &str) -> Option<&Track> {
+        self.state.get_track(name)
+    }
+
+    /// Get a snapshot of the tracks in the session.
+    ///
+    /// See [`RocketClient::save_tracks`](crate::RocketClient::save_tracks).
+    pub fn save_tracks(&self) -> &Tracks {
+        self.state.tracks()
+    }
+
+    /// Get a counter that changes whenever the tracker sets or deletes a key in the named track.
+    ///
+    /// See [`RocketClient::get_track_generation`](crate::RocketClient::get_track_generation).
+    pub fn get_track_generation(&self, name: &str) -> Option<u64> {
+        self.state.track_generation(name)
+    }
+
+    /// Enable or disable [`Event::KeySet`] and [`Event::KeyDeleted`] events. They are disabled by default.
+    pub fn set_key_events(&mut self, enabled: bool) {
+        self.state.set_key_events(enabled);
+    }
+
+    /// Send a SetRow message.
+    ///
+    /// This changes the current row on the tracker side.
+    ///
+    /// # Errors
+    ///
+    /// This method can return an [`Error::IOError`] if Rocket tracker disconnects.
+    pub async fn set_row(&mut self, row: u32) -> Result<(), Error> {
+        // Send SET_ROW message
+        let mut buf = Vec::new();
+        crate::protocol::encode_set_row(&mut buf, row);
+        self.stream.write_all(&buf).await.map_err(Error::IOError)
+    }
+
+    /// Wait for the next event from the tracker.
+    ///
+    /// Key changes from the tracker are applied to the tracks while waiting.
+    ///
+    /// # Errors
+    ///
+    /// This method can return an [`Error::IOError`] if the rocket tracker disconnects,
+    /// or a protocol error if the tracker sends malformed data, see [`Error::is_recoverable`].
+    pub async fn next_event(&mut self) -> Result<Event, Error> {
+        NextEvent { client: self }.await
+    }
+
+    /// Get a [`Stream`] of events from the tracker.
+    ///
+    /// The stream never ends, but yields an error when the tracker disconnects or sends malformed data.
+    /// The stream borrows the client, so drop it before calling other methods, e.g. between frames.
+    pub fn events(&mut self) -> Events<'_, S> {
+        Events { client: self }
+    }
+
+    /// Attempt to receive the next event from the tracker.
+    ///
+    /// This is the poll-based counterpart of [`next_event`](Self::next_event).
+    pub fn poll_event(&mut self, cx: &mut Context<'_>) -> Poll<Result<Event, Error>> {
+        loop {
+            match self.decoder.decode() {
+                Ok(Some(command)) => match self.state.process_command(command) {
+                    Ok(Some(event)) => return Poll::Ready(Ok(event)),
+                    Ok(None) => {}
+                    Err(e) => return Poll::Ready(Err(e)),
+                },
+                Ok(None) => {
+                    let mut read_buf = ReadBuf::new(&mut self.receive_buf);
+                    match Pin::new(&mut self.stream).poll_read(cx, &mut read_buf) {
+                        Poll::Ready(Ok(())) if read_buf.filled().is_empty() => {
+                            let eof = io::ErrorKind::UnexpectedEof.into();
+                            return Poll::Ready(Err(Error::IOError(eof)));
+                        }
+                        Poll::Ready(Ok(())) => self.decoder.feed(read_buf.filled()),
+                        Poll::Ready(Err(e)) => return Poll::Ready(Err(Error::IOError(e))),
+                        Poll::Pending => return Poll::Pending,
+                    }
+                }
+                Err(e) => return Poll::Ready(Err(e.into())),
+            }
+        }
+    }
+
+    async fn handshake(&mut self) -> Result<(), Error> {
+        self.stream
+            .write_all(CLIENT_GREETING)
+            .await
+            .map_err(Error::Handshake)?;
+
+        let mut handshake = Handshake::new();
+        let mut buf = [0; SERVER_GREETING.len()];
+        while !handshake.check()? {
+            let buf = &mut buf[..handshake.remaining()];
+            self.stream
+                .read_exact(buf)
+                .await
+                .map_err(Error::Handshake)?;
+            handshake.feed(buf);
+        }
+        Ok(())
+    }
+}
+
+/// A [`Stream`] of events from the tracker, returned by [`AsyncRocketClient::events`].
+#[derive(Debug)]
+pub struct Events<'a, S> {
+    client: &'a mut AsyncRocketClient<S>,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Stream for Events<'_, S> {
+    type Item = Result<Event, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.client.poll_event(cx).map(Some)
+    }
+}
+
+/// Future returned by [`AsyncRocketClient::next_event`].
+struct NextEvent<'a, S> {
+    client: &'a mut AsyncRocketClient<S>,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Future for NextEvent<'_, S> {
+    type Output = Result<Event, Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.client.poll_event(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpolation::Interpolation;
+    use crate::protocol::{ClientCommand, ClientDecoder, Command};
+    use crate::track::Key;
+    use tokio::io::DuplexStream;
+
+    async fn connected_client() -> (AsyncRocketClient<DuplexStream>, DuplexStream) {
+        let (client_end, mut tracker_end) = tokio::io::duplex(1024);
+        tracker_end.write_all(SERVER_GREETING).await.unwrap();
+        let rocket = AsyncRocketClient::from_stream(client_end).await.unwrap();
+
+        let mut greeting = [0; CLIENT_GREETING.len()];
+        tracker_end.read_exact(&mut greeting).await.unwrap();
+        assert_eq!(greeting, CLIENT_GREETING);
+        (rocket, tracker_end)
+    }
+
+    async fn send(tracker_end: &mut DuplexStream, command: Command) {
+        let mut buf = Vec::new();
+        command.encode(&mut buf);
+        tracker_end.write_all(&buf).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn requests() {
+        let (mut rocket, mut tracker_end) = connected_client().await;
+        rocket.get_track_mut("a").await.unwrap();
+        rocket.get_track_mut("a").await.unwrap();
+        rocket.set_row(3).await.unwrap();
+        drop(rocket);
+
+        let mut buf = Vec::new();
+        tracker_end.read_to_end(&mut buf).await.unwrap();
+        let mut decoder = ClientDecoder::new();
+        decoder.feed(&buf);
+        assert_eq!(
+            decoder.decode(),
+            Ok(Some(ClientCommand::GetTrack("a".into())))
+        );
+        assert_eq!(decoder.decode(), Ok(Some(ClientCommand::SetRow(3))));
+        assert_eq!(decoder.decode(), Ok(None));
+    }
+
+    #[tokio::test]
+    async fn events() {
+        let (mut rocket, mut tracker_end) = connected_client().await;
+        rocket.get_track_mut("a").await.unwrap();
+
+        let key = Key::new(2, 1.0, Interpolation::Ramp);
+        send(&mut tracker_end, Command::SetKey { track: 0, key }).await;
+        send(&mut tracker_end, Command::Pause(true)).await;
+        send(&mut tracker_end, Command::SetRow(2)).await;
+
+        assert!(matches!(rocket.next_event().await, Ok(Event::Pause(true))));
+        assert_eq!(rocket.get_track("a").unwrap().key_at(2), Some(&key));
+
+        let mut events = rocket.events();
+        let event = std::future::poll_fn(|cx| Pin::new(&mut events).poll_next(cx)).await;
+        assert!(matches!(event, Some(Ok(Event::SetRow(2)))));
+
+        drop(tracker_end);
+        assert!(matches!(rocket.next_event().await, Err(Error::IOError(_))));
+    }
+}
@@ -0,0 +1,240 @@
+//! Finding stale and forgotten tracks, [`AuditReport`].
+//!
+//! [`RocketClient`](crate::RocketClient), [`RocketPlayer`](crate::RocketPlayer) and
+//! [`simple::Rocket`](crate::simple::Rocket) record which tracks are read. A track is read when it's looked up with
+//! `get_track` or `get_track_by_id`, or its value is requested with `get_value` or `get_value_by_id`.
+//! Getting a track that already exists from a client with `get_track_mut` or `get_track_id` is also a read.
+//! Creating a track, e.g. with [`RocketTracks::register`](crate::RocketTracks::register), or getting a handle
+//! from [`RocketPlayer::get_track_id`](crate::RocketPlayer::get_track_id), doesn't count as a read.
+//! After running the production, e.g. at exit, their `audit` methods report:
+//!
+//! - Unused tracks, which are in the data but were never read. These can be deleted from the tracker.
+//! - Missing tracks, which were requested but aren't in the data, e.g. because the tracks weren't saved after
+//!   adding them. See [`MissingTrackPolicy`](crate::player::MissingTrackPolicy).
+//! - Empty tracks, which were read but have no keys.
+//! - Constant tracks, which were read and have two or more keys, which all have the same value.
+//!
+//! Each track is reported at most once, under the first of these that applies.
+//!
+//! # Usage
+//!
+//! ```rust
+//! # use rust_rocket::{RocketPlayer, Track};
+//! # let tracks = vec![Track::new("camera:x"), Track::new("stale")];
+//! let player = RocketPlayer::new(tracks);
+//!
+//! // Play the production...
+//! # player.get_track("camera:x");
+//!
+//! let report = player.audit();
+//! if !report.is_clean() {
+//!     eprintln!("{}", report);
+//! }
+//! # assert_eq!(report.get_unused_tracks(), ["stale"]);
+//! ```
+
+use crate::track::Track;
+use alloc::{string::String, vec::Vec};
+use core::{
+    fmt,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// A report of tracks that are unused, missing, empty or constant, see the [module documentation](self).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuditReport {
+    unused: Vec<String>,
+    missing: Vec<String>,
+    empty: Vec<String>,
+    constant: Vec<String>,
+}
+
+impl AuditReport {
+    /// Audit `tracks` against the names of the tracks that were requested.
+    ///
+    /// Use this to compare a saved file with an editing session, e.g. with the tracks of a
+    /// [`RocketClient`](crate::RocketClient) as `requested`, to find stale tracks in the file.
+    pub fn new<'a>(tracks: &[Track], requested: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut read = Vec::new();
+        read.resize(tracks.len(), false);
+        let mut missing = Vec::new();
+        for name in requested {
+            match tracks.iter().position(|track| track.get_name() == name) {
+                Some(index) => read[index] = true,
+                None if !missing.contains(&name) => missing.push(name),
+                None => (),
+            }
+        }
+        Self::from_reads(tracks, |index| read[index], missing)
+    }
+
+    /// Audit `tracks`, where `read` tells if the track at an index was read.
+    pub(crate) fn from_reads<'a>(
+        tracks: &[Track],
+        read: impl Fn(usize) -> bool,
+        missing: impl IntoIterator<Item = &'a str>,
+    ) -> Self {
+        let mut report = AuditReport {
+            missing: missing.into_iter().map(String::from).collect(),
+            ..AuditReport::default()
+        };
+        for (index, track) in tracks.iter().enumerate() {
+            let name = String::from(track.get_name());
+            let keys = track.keys();
+            if !read(index) {
+                report.unused.push(name);
+            } else if keys.is_empty() {
+                report.empty.push(name);
+            } else if keys.len() > 1
+                && keys
+                    .iter()
+                    .all(|key| key.get_value() == keys[0].get_value())
+            {
+                report.constant.push(name);
+            }
+        }
+        report
+    }
+
+    /// Get the names of the tracks that are in the data but were never read.
+    pub fn get_unused_tracks(&self) -> &[String] {
+        &self.unused
+    }
+
+    /// Get the names of the tracks that were requested but aren't in the data, in the order they were requested.
+    pub fn get_missing_tracks(&self) -> &[String] {
+        &self.missing
+    }
+
+    /// Get the names of the tracks that were read but have no keys.
+    pub fn get_empty_tracks(&self) -> &[String] {
+        &self.empty
+    }
+
+    /// Get the names of the tracks that were read and have two or more keys, which all have the same value.
+    pub fn get_constant_tracks(&self) -> &[String] {
+        &self.constant
+    }
+
+    /// Returns true if no tracks were reported.
+    pub fn is_clean(&self) -> bool {
+        self.unused.is_empty()
+            && self.missing.is_empty()
+            && self.empty.is_empty()
+            && self.constant.is_empty()
+    }
+}
+
+/// Lists the reported tracks by category, one category per line.
+impl fmt::Display for AuditReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_clean() {
+            return write!(f, "All tracks are used and have keys");
+        }
+        let categories = [
+            ("Unused tracks", &self.unused),
+            ("Missing tracks", &self.missing),
+            ("Empty tracks", &self.empty),
+            ("Constant tracks", &self.constant),
+        ];
+        let mut first = true;
+        for (title, names) in categories {
+            if names.is_empty() {
+                continue;
+            }
+            if !first {
+                writeln!(f)?;
+            }
+            first = false;
+            write!(f, "{}: {}", title, names.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Per-track read flags, parallel to a list of tracks.
+///
+/// Atomic, so tracks can be marked as read through a shared reference without losing `Sync`.
+#[derive(Debug, Default)]
+pub(crate) struct Reads(Vec<AtomicBool>);
+
+impl Reads {
+    pub(crate) fn new(len: usize) -> Self {
+        Self((0..len).map(|_| AtomicBool::new(false)).collect())
+    }
+
+    /// Add a flag for a new track. Only the client adds tracks.
+    #[cfg(feature = "std")]
+    pub(crate) fn push(&mut self) {
+        self.0.push(AtomicBool::new(false));
+    }
+
+    pub(crate) fn mark(&self, index: usize) {
+        if let Some(read) = self.0.get(index) {
+            read.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn get(&self, index: usize) -> bool {
+        self.0
+            .get(index)
+            .map_or(false, |read| read.load(Ordering::Relaxed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpolation::Interpolation;
+    use crate::track::Key;
+    use alloc::vec;
+
+    fn test_tracks() -> Vec<Track> {
+        let mut changing = Track::new("changing");
+        changing.set_key(Key::new(0, 1.0, Interpolation::Linear));
+        changing.set_key(Key::new(8, 2.0, Interpolation::Step));
+        let mut constant = Track::new("constant");
+        constant.set_key(Key::new(0, 1.0, Interpolation::Linear));
+        constant.set_key(Key::new(8, 1.0, Interpolation::Step));
+        let mut single = Track::new("single");
+        single.set_key(Key::new(0, 1.0, Interpolation::Step));
+        vec![
+            changing,
+            constant,
+            single,
+            Track::new("empty"),
+            Track::new("stale"),
+        ]
+    }
+
+    #[test]
+    fn report() {
+        let report = AuditReport::new(
+            &test_tracks(),
+            ["changing", "constant", "single", "empty", "new", "changing"],
+        );
+        assert_eq!(report.get_unused_tracks(), ["stale"]);
+        assert_eq!(report.get_missing_tracks(), ["new"]);
+        assert_eq!(report.get_empty_tracks(), ["empty"]);
+        assert_eq!(report.get_constant_tracks(), ["constant"]);
+        assert!(!report.is_clean());
+        assert_eq!(
+            report.to_string(),
+            "Unused tracks: stale\nMissing tracks: new\nEmpty tracks: empty\nConstant tracks: constant"
+        );
+
+        let report = AuditReport::new(&test_tracks()[..1], ["changing"]);
+        assert!(report.is_clean());
+        assert_eq!(report.to_string(), "All tracks are used and have keys");
+    }
+
+    #[test]
+    fn reads() {
+        let reads = Reads::new(2);
+        reads.mark(1);
+        reads.mark(2);
+        assert!(!reads.get(0));
+        assert!(reads.get(1));
+        assert!(!reads.get(2));
+    }
+}
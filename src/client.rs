@@ -14,6 +14,12 @@
 //!
 //! See the linked documentation items and the examples-directory for more examples.
 //!
+//! # Other transports
+//!
+//! [`RocketClient`] uses a [`TcpStream`] by default, but it works on top of any [`Read`] + [`Write`] stream,
+//! e.g. a Unix domain socket or a tunneled connection.
+//! Connect the stream yourself and pass it to [`RocketClient::from_stream`].
+//!
 //! # Tips
 //!
 //! The library is agnostic to your source of time. In a typical production, some kind of music player library
@@ -72,6 +78,8 @@
 //!                 Event::SetRow(to) => seek = Some(to),
 //!                 Event::Pause(state) => music.pause(state),
 //!                 Event::SaveTracks => {/* Call save_tracks and write to a file */}
+//!                 Event::Reconnected => {}
+//!                 Event::KeySet { .. } | Event::KeyDeleted { .. } => {}
 //!             }
 //!         }
 //!         // When using the low level API, it's recommended to call set_time only when the not seeking.
@@ -86,38 +94,26 @@
 //!     }
 //! }
 //! ```
-use crate::interpolation::*;
+use crate::audit::{AuditReport, Reads};
+use crate::protocol::{
+    self, Command, DecodeError, Decoder, GreetingMismatch, Handshake, CLIENT_GREETING,
+    SERVER_GREETING,
+};
 use crate::track::*;
 use crate::Tracks;
 
-use byteorder::ByteOrder;
-use byteorder::{BigEndian, ReadBytesExt};
-use std::hint::unreachable_unchecked;
 use std::{
     convert::TryFrom,
-    io::{self, Cursor, Read, Write},
-    net::{TcpStream, ToSocketAddrs},
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    time::Duration,
 };
 use thiserror::Error;
 
-// Rocket protocol commands
-const CLIENT_GREETING: &[u8] = b"hello, synctracker!";
-const SERVER_GREETING: &[u8] = b"hello, demo!";
-
-const SET_KEY: u8 = 0;
-const DELETE_KEY: u8 = 1;
-const GET_TRACK: u8 = 2;
-const SET_ROW: u8 = 3;
-const PAUSE: u8 = 4;
-const SAVE_TRACKS: u8 = 5;
-
-const SET_KEY_LEN: usize = 4 + 4 + 4 + 1;
-const DELETE_KEY_LEN: usize = 4 + 4;
-const GET_TRACK_LEN: usize = 4; // Does not account for name length
-const SET_ROW_LEN: usize = 4;
-const PAUSE_LEN: usize = 1;
-
-const MAX_COMMAND_LEN: usize = SET_KEY_LEN;
+/// Size of the buffer for reads from the tracker.
+///
+/// The tracker sends every key of a track at once, so a large buffer avoids one read per key.
+pub(crate) const RECEIVE_BUFFER_LEN: usize = 64 * 1024;
 
 /// The `Error` Type. This is the main error type.
 #[derive(Debug, Error)]
@@ -138,13 +134,66 @@ pub enum Error {
     /// Network IO error during operation
     #[error("Rocket tracker disconnected")]
     IOError(#[source] std::io::Error),
+    /// Connecting or the handshake took longer than allowed by [`ConnectOptions`]
+    #[error("Timed out waiting for the Rocket tracker")]
+    Timeout,
+    /// The tracker sent a key command for a track index that the client hasn't requested.
+    /// The command was ignored.
+    #[error("The Rocket tracker sent a key for unknown track index {0}")]
+    UnknownTrackIndex(u32),
+    /// The tracker sent a command byte that is not part of the protocol.
+    /// Buffered input was discarded, because the rest of the stream can't be decoded reliably.
+    /// You should reconnect.
+    #[error("The Rocket tracker sent an unknown command {0}")]
+    UnknownCommand(u8),
+    /// The tracker sent a key with an unknown interpolation type. The key was ignored.
+    #[error("The Rocket tracker sent a key with unknown interpolation {0}")]
+    InvalidInterpolation(u8),
 }
 
-#[derive(Debug)]
-enum ClientState {
-    New,
-    Incomplete(usize),
-    Complete,
+impl Error {
+    /// Returns `true` if the connection is still usable after this error,
+    /// i.e. you can keep polling events without reconnecting.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use rust_rocket::{server::MockTracker, RocketClient};
+    /// # let (_tracker, stream) = MockTracker::duplex();
+    /// # let mut rocket = RocketClient::from_stream(stream)?;
+    /// loop {
+    ///     match rocket.poll_events() {
+    ///         Ok(Some(_event)) => { /* Handle the event */ }
+    ///         Ok(None) => break,
+    ///         Err(e) if e.is_recoverable() => eprintln!("{}", e),
+    ///         Err(e) => return Err(e),
+    ///     }
+    /// }
+    /// # Ok::<(), rust_rocket::client::Error>(())
+    /// ```
+    pub fn is_recoverable(&self) -> bool {
+        matches!(
+            self,
+            Error::UnknownTrackIndex(_) | Error::InvalidInterpolation(_)
+        )
+    }
+}
+
+impl From<GreetingMismatch> for Error {
+    fn from(e: GreetingMismatch) -> Self {
+        Error::HandshakeGreetingMismatch(e.0)
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(e: DecodeError) -> Self {
+        match e {
+            DecodeError::UnknownCommand(cmd) => Error::UnknownCommand(cmd),
+            DecodeError::InvalidInterpolation(raw) => Error::InvalidInterpolation(raw),
+            // Only clients send GET_TRACK, so it's an unknown command from the tracker
+            DecodeError::TrackNameTooLong(_) => Error::UnknownCommand(protocol::GET_TRACK),
+        }
+    }
 }
 
 /// The `Event` Type. These are the various events from the tracker.
@@ -157,22 +206,319 @@ pub enum Event {
     /// The tracker asks us to save our track data.
     /// You may want to call [`RocketClient::save_tracks`] after receiving this event.
     SaveTracks,
+    /// The client has reconnected to the tracker, see [`RocketClient::reconnect`].
+    ///
+    /// The tracks are being refreshed with keys from the tracker.
+    Reconnected,
+    /// The tracker set a key, which has already been applied to the track.
+    ///
+    /// Only sent if enabled with [`RocketClient::set_key_events`].
+    KeySet {
+        /// Index of the track in [`RocketClient::save_tracks`].
+        track: usize,
+        /// The new key, see [`Key::get_row`] for its row.
+        key: Key,
+    },
+    /// The tracker deleted a key, which has already been removed from the track.
+    ///
+    /// Only sent if enabled with [`RocketClient::set_key_events`].
+    KeyDeleted {
+        /// Index of the track in [`RocketClient::save_tracks`].
+        track: usize,
+        /// The row of the deleted key.
+        row: u32,
+    },
 }
 
-#[derive(Debug)]
-enum ReceiveResult {
-    Some(Event),
-    None,
-    Incomplete,
+/// Track state of a client session, shared by the client implementations.
+#[derive(Debug, Default)]
+pub(crate) struct TrackState {
+    tracks: Vec<Track>,
+    /// Tracks whose keys will be replaced by the next SET_KEY after a reconnect
+    stale: Vec<bool>,
+    /// Per-track change counters, parallel to `tracks`
+    generations: Vec<u64>,
+    generation: u64,
+    key_events: bool,
+    /// Which tracks have been read, parallel to `tracks`
+    reads: Reads,
+}
+
+impl TrackState {
+    pub(crate) fn tracks(&self) -> &Tracks {
+        &self.tracks
+    }
+
+    pub(crate) fn get_track(&self, name: &str) -> Option<&Track> {
+        self.position(name)
+            .and_then(|index| self.get_track_by_id(TrackId::new(index)))
+    }
+
+    pub(crate) fn get_track_by_id(&self, id: TrackId) -> Option<&Track> {
+        self.reads.mark(id.index());
+        self.tracks.get(id.index())
+    }
+
+    /// Tracks are only created when requested, so none are missing.
+    pub(crate) fn audit(&self) -> AuditReport {
+        AuditReport::from_reads(&self.tracks, |index| self.reads.get(index), [])
+    }
+
+    /// A counter that changes whenever a track is added or the tracker changes a key.
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// A counter that changes whenever the tracker changes a key in the named track.
+    pub(crate) fn track_generation(&self, name: &str) -> Option<u64> {
+        self.position(name).map(|index| self.generations[index])
+    }
+
+    pub(crate) fn set_key_events(&mut self, enabled: bool) {
+        self.key_events = enabled;
+    }
+
+    /// Get a handle to an existing track, which counts as reading it.
+    pub(crate) fn existing_track_id(&self, name: &str) -> Option<TrackId> {
+        let index = self.position(name)?;
+        self.reads.mark(index);
+        Some(TrackId::new(index))
+    }
+
+    pub(crate) fn position(&self, name: &str) -> Option<usize> {
+        self.tracks.iter().position(|t| t.get_name() == name)
+    }
+
+    pub(crate) fn track_mut(&mut self, index: usize) -> &mut Track {
+        &mut self.tracks[index]
+    }
+
+    /// Add a new track. The caller sends the GET_TRACK message for it.
+    pub(crate) fn push(&mut self, name: &str) -> TrackId {
+        self.tracks.push(Track::new(name));
+        self.stale.push(false);
+        self.generations.push(0);
+        self.reads.push();
+        self.generation += 1;
+        TrackId::new(self.tracks.len() - 1)
+    }
+
+    /// Encode GET_TRACK messages for every track into `buf`, in the original order.
+    pub(crate) fn encode_refresh(&self, buf: &mut Vec<u8>) {
+        for track in &self.tracks {
+            protocol::encode_get_track(buf, track.get_name());
+        }
+    }
+
+    /// Mark every track's keys to be replaced by the next keys from the tracker.
+    pub(crate) fn mark_stale(&mut self) {
+        self.stale.iter_mut().for_each(|stale| *stale = true);
+    }
+
+    /// Apply a command from the tracker, returning the event it produces, if any.
+    ///
+    /// Key commands for unknown track indices return [`Error::UnknownTrackIndex`] and change nothing.
+    pub(crate) fn process_command(&mut self, command: Command) -> Result<Option<Event>, Error> {
+        match command {
+            Command::SetKey { track, key } => {
+                let index = self.index(track)?;
+                if self.stale[index] {
+                    self.stale[index] = false;
+                    self.tracks[index].clear_keys();
+                }
+                self.tracks[index].set_key(key);
+                self.changed(index);
+                Ok(self.key_events.then(|| Event::KeySet { track: index, key }))
+            }
+            Command::DeleteKey { track, row } => {
+                let index = self.index(track)?;
+                self.tracks[index].delete_key(row);
+                self.changed(index);
+                Ok(self
+                    .key_events
+                    .then(|| Event::KeyDeleted { track: index, row }))
+            }
+            Command::SetRow(row) => Ok(Some(Event::SetRow(row))),
+            Command::Pause(flag) => Ok(Some(Event::Pause(flag))),
+            Command::SaveTracks => Ok(Some(Event::SaveTracks)),
+        }
+    }
+
+    fn changed(&mut self, index: usize) {
+        self.generations[index] += 1;
+        self.generation += 1;
+    }
+
+    fn index(&self, track: u32) -> Result<usize, Error> {
+        usize::try_from(track)
+            .ok()
+            .filter(|&index| index < self.tracks.len())
+            .ok_or(Error::UnknownTrackIndex(track))
+    }
+}
+
+/// Options for connecting a [`RocketClient`] to a tracker over TCP.
+///
+/// The options are kept by the client and also used by [`RocketClient::reconnect`].
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use rust_rocket::client::ConnectOptions;
+/// # use std::time::Duration;
+/// let mut rocket = ConnectOptions::new()
+///     .address("192.168.1.10:1338")
+///     .connect_timeout(Some(Duration::from_secs(1)))
+///     .nodelay(true)
+///     .connect()?;
+/// # Ok::<(), rust_rocket::client::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConnectOptions {
+    address: String,
+    connect_timeout: Option<Duration>,
+    handshake_timeout: Option<Duration>,
+    nodelay: bool,
+}
+
+impl Default for ConnectOptions {
+    fn default() -> Self {
+        Self {
+            address: String::from("localhost:1338"),
+            connect_timeout: None,
+            handshake_timeout: Some(Duration::from_secs(5)),
+            nodelay: false,
+        }
+    }
+}
+
+impl ConnectOptions {
+    /// Construct the default options.
+    ///
+    /// The default address is `localhost:1338`, the connect timeout is left to the operating system,
+    /// the handshake timeout is 5 seconds, and `TCP_NODELAY` is not set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the tracker address, in a form accepted by [`ToSocketAddrs`] for `&str`, e.g. `"localhost:1338"`.
+    pub fn address(mut self, address: impl Into<String>) -> Self {
+        self.address = address.into();
+        self
+    }
+
+    /// Set the timeout for establishing the TCP connection to each resolved address, or `None` to wait
+    /// as long as the operating system allows.
+    ///
+    /// A zero duration is treated as `None`.
+    pub fn connect_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.connect_timeout = timeout.filter(|t| *t != Duration::ZERO);
+        self
+    }
+
+    /// Set the deadline for the greeting exchange after the connection has been established,
+    /// or `None` to wait forever.
+    ///
+    /// A zero duration is treated as `None`.
+    pub fn handshake_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.handshake_timeout = timeout.filter(|t| *t != Duration::ZERO);
+        self
+    }
+
+    /// Set the `TCP_NODELAY` option of the socket, see [`TcpStream::set_nodelay`].
+    pub fn nodelay(mut self, nodelay: bool) -> Self {
+        self.nodelay = nodelay;
+        self
+    }
+
+    /// Connect to the tracker.
+    ///
+    /// Every address that the address resolves to is tried in turn, until both the connection and the handshake
+    /// succeed.
+    ///
+    /// # Errors
+    ///
+    /// The error for the last address: [`Error::Connect`] if connection cannot be established,
+    /// [`Error::Handshake`] if the handshake fails, or [`Error::Timeout`] if either takes longer than allowed.
+    pub fn connect(&self) -> Result<RocketClient, Error> {
+        RocketClient::connect_with(self.address.as_str(), self)
+    }
+
+    /// Connect to the first address that accepts the connection and the handshake.
+    ///
+    /// The stream is left in blocking mode with the handshake timeouts, see [`finish`](Self::finish).
+    fn open(&self, addr: impl ToSocketAddrs) -> Result<(TcpStream, SocketAddr), Error> {
+        let mut last_error = None;
+        for addr in addr.to_socket_addrs().map_err(Error::Connect)? {
+            let result = match self.connect_timeout {
+                Some(timeout) => TcpStream::connect_timeout(&addr, timeout),
+                None => TcpStream::connect(addr),
+            };
+            let error = match result {
+                Ok(stream) => match self.handshake(stream) {
+                    Ok(stream) => return Ok((stream, addr)),
+                    Err(e) => e,
+                },
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => Error::Timeout,
+                Err(e) => Error::Connect(e),
+            };
+            last_error = Some(error);
+        }
+        Err(last_error.unwrap_or_else(|| {
+            Error::Connect(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Address resolved to no addresses",
+            ))
+        }))
+    }
+
+    fn handshake(&self, mut stream: TcpStream) -> Result<TcpStream, Error> {
+        stream.set_nodelay(self.nodelay).map_err(Error::Connect)?;
+        stream
+            .set_read_timeout(self.handshake_timeout)
+            .map_err(Error::Connect)?;
+        stream
+            .set_write_timeout(self.handshake_timeout)
+            .map_err(Error::Connect)?;
+
+        match handshake(&mut stream) {
+            Err(Error::Handshake(e)) if is_timeout(&e) => Err(Error::Timeout),
+            result => result.map(|()| stream),
+        }
+    }
+
+    /// Clear the handshake timeouts of a stream from [`open`](Self::open) and switch it to non-blocking mode.
+    fn finish(stream: &TcpStream) -> Result<(), Error> {
+        stream.set_read_timeout(None).map_err(Error::Connect)?;
+        stream.set_write_timeout(None).map_err(Error::Connect)?;
+        stream.set_nonblocking(true).map_err(Error::SetNonblocking)
+    }
+}
+
+/// Timeouts are reported as WouldBlock on Unix and TimedOut on Windows.
+fn is_timeout(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
 }
 
 /// The `RocketClient` type. This contains the connected socket and other fields.
+///
+/// The stream type `S` is [`TcpStream`] unless the client is constructed with [`RocketClient::from_stream`].
 #[derive(Debug)]
-pub struct RocketClient {
-    stream: TcpStream,
-    state: ClientState,
-    cmd: Vec<u8>,
-    tracks: Vec<Track>,
+pub struct RocketClient<S = TcpStream> {
+    stream: S,
+    decoder: Decoder,
+    state: TrackState,
+    addr: Option<SocketAddr>,
+    options: ConnectOptions,
+    reconnected: bool,
+    receive_buf: Box<[u8]>,
+    /// Encoded messages that haven't been written to the stream yet
+    send_queue: Vec<u8>,
+    /// The latest row from [`RocketClient::set_row`], sent when `send_queue` is empty
+    pending_row: Option<u32>,
 }
 
 impl RocketClient {
@@ -182,8 +528,8 @@ impl RocketClient {
     ///
     /// # Errors
     ///
-    /// [`Error::Connect`] if connection cannot be established, or [`Error::Handshake`]
-    /// if the handshake fails.
+    /// [`Error::Connect`] if connection cannot be established, [`Error::Handshake`]
+    /// if the handshake fails, or [`Error::Timeout`] if the tracker doesn't answer the handshake.
     ///
     /// # Examples
     ///
@@ -193,17 +539,18 @@ impl RocketClient {
     /// # Ok::<(), rust_rocket::client::Error>(())
     /// ```
     pub fn new() -> Result<Self, Error> {
-        Self::connect(("localhost", 1338))
+        ConnectOptions::new().connect()
     }
 
     /// Construct a new RocketClient.
     ///
-    /// This constructs a new Rocket client and connects to a specified host and port.
+    /// This constructs a new Rocket client and connects to a specified host and port,
+    /// with the default [`ConnectOptions`].
     ///
     /// # Errors
     ///
-    /// [`Error::Connect`] if connection cannot be established, or [`Error::Handshake`]
-    /// if the handshake fails.
+    /// [`Error::Connect`] if connection cannot be established, [`Error::Handshake`]
+    /// if the handshake fails, or [`Error::Timeout`] if the tracker doesn't answer the handshake.
     ///
     /// # Examples
     ///
@@ -213,23 +560,197 @@ impl RocketClient {
     /// # Ok::<(), rust_rocket::client::Error>(())
     /// ```
     pub fn connect(addr: impl ToSocketAddrs) -> Result<Self, Error> {
-        let stream = TcpStream::connect(addr).map_err(Error::Connect)?;
+        Self::connect_with(addr, &ConnectOptions::new())
+    }
 
-        let mut rocket = Self {
-            stream,
-            state: ClientState::New,
-            cmd: Vec::new(),
-            tracks: Vec::new(),
+    /// Construct a new RocketClient, giving up if connecting or the handshake takes longer than `timeout`.
+    ///
+    /// Each phase has its own deadline, see [`ConnectOptions`] for finer control.
+    ///
+    /// # Errors
+    ///
+    /// [`Error::Timeout`] if the connection or the handshake takes longer than `timeout`,
+    /// [`Error::Connect`] if connection cannot be established, or [`Error::Handshake`] if the handshake fails.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use rust_rocket::RocketClient;
+    /// # use std::time::Duration;
+    /// let mut rocket = RocketClient::connect_timeout(("localhost", 1338), Duration::from_millis(500))?;
+    /// # Ok::<(), rust_rocket::client::Error>(())
+    /// ```
+    pub fn connect_timeout(addr: impl ToSocketAddrs, timeout: Duration) -> Result<Self, Error> {
+        let options = ConnectOptions::new()
+            .connect_timeout(Some(timeout))
+            .handshake_timeout(Some(timeout));
+        Self::connect_with(addr, &options)
+    }
+
+    fn connect_with(addr: impl ToSocketAddrs, options: &ConnectOptions) -> Result<Self, Error> {
+        let (stream, addr) = options.open(addr)?;
+        ConnectOptions::finish(&stream)?;
+
+        let mut rocket = Self::with_stream(stream);
+        rocket.addr = Some(addr);
+        rocket.options = options.clone();
+        Ok(rocket)
+    }
+
+    /// Reconnect to the tracker at the same address, keeping the tracks.
+    ///
+    /// See [`reconnect_with`](Self::reconnect_with) for details.
+    ///
+    /// # Limitations
+    ///
+    /// The protocol has no message for a track without keys, so a track that has no keys in the tracker after
+    /// reconnecting, e.g. because the tracker was restarted without saving, keeps its keys from before the reconnect.
+    /// They are replaced when the tracker sets a key in the track. To drop them, create a new client instead.
+    ///
+    /// # Errors
+    ///
+    /// [`Error::Connect`] if connection cannot be established, [`Error::Handshake`]
+    /// if the handshake fails, or [`Error::Timeout`] if either takes longer than the client's [`ConnectOptions`] allow.
+    /// The client is left unchanged on error, so you can retry later.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use rust_rocket::client::{Event, RocketClient};
+    /// # let mut rocket = RocketClient::new()?;
+    /// loop {
+    ///     match rocket.poll_events() {
+    ///         Ok(Some(Event::Reconnected)) => println!("Reconnected"),
+    ///         Ok(Some(_event)) => { /* Handle other events */ }
+    ///         Ok(None) => break,
+    ///         Err(_) => {
+    ///             // Tracker disconnected, try again on the next frame
+    ///             if rocket.reconnect().is_err() {
+    ///                 break;
+    ///             }
+    ///         }
+    ///     }
+    /// }
+    /// # Ok::<(), rust_rocket::client::Error>(())
+    /// ```
+    pub fn reconnect(&mut self) -> Result<(), Error> {
+        let addr = match self.addr {
+            Some(addr) => addr,
+            None => self.stream.peer_addr().map_err(Error::Connect)?,
         };
+        let (mut stream, addr) = self.options.open(addr)?;
+        // The handshake timeout also applies to sending the refresh
+        match self.send_refresh(&mut stream) {
+            Err(Error::IOError(e)) if is_timeout(&e) => return Err(Error::Timeout),
+            result => result?,
+        }
+        ConnectOptions::finish(&stream)?;
 
-        rocket.handshake()?;
+        self.replace_stream(stream);
+        self.addr = Some(addr);
+        Ok(())
+    }
+}
 
-        rocket
-            .stream
-            .set_nonblocking(true)
-            .map_err(Error::SetNonblocking)?;
+impl<S: Read + Write> RocketClient<S> {
+    /// Construct a new RocketClient from a stream that is already connected to a tracker.
+    ///
+    /// This performs the handshake, which needs blocking reads. After that, [`poll_events`](Self::poll_events)
+    /// treats [`io::ErrorKind::WouldBlock`] as "no data available", so you should switch the stream to
+    /// non-blocking mode (see [`get_mut`](Self::get_mut)) if it supports it.
+    /// Otherwise `poll_events` blocks until the tracker sends something.
+    ///
+    /// # Errors
+    ///
+    /// [`Error::Handshake`] if the handshake fails.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # #[cfg(unix)]
+    /// # {
+    /// # use rust_rocket::RocketClient;
+    /// use std::os::unix::net::UnixStream;
+    ///
+    /// let stream = UnixStream::connect("/tmp/rocket.sock").expect("Failed to connect");
+    /// let mut rocket = RocketClient::from_stream(stream)?;
+    /// rocket
+    ///     .get_mut()
+    ///     .set_nonblocking(true)
+    ///     .expect("Failed to set nonblocking mode");
+    /// # }
+    /// # Ok::<(), rust_rocket::client::Error>(())
+    /// ```
+    pub fn from_stream(stream: S) -> Result<Self, Error> {
+        let mut stream = stream;
+        handshake(&mut stream)?;
 
-        Ok(rocket)
+        Ok(Self::with_stream(stream))
+    }
+
+    /// Construct a client around a stream that has completed the handshake.
+    fn with_stream(stream: S) -> Self {
+        Self {
+            stream,
+            decoder: Decoder::new(),
+            state: TrackState::default(),
+            addr: None,
+            options: ConnectOptions::default(),
+            reconnected: false,
+            receive_buf: vec![0; RECEIVE_BUFFER_LEN].into_boxed_slice(),
+            send_queue: Vec::new(),
+            pending_row: None,
+        }
+    }
+
+    /// Replace the connection with a new stream that is connected to a tracker, keeping the tracks.
+    ///
+    /// This performs the handshake like [`from_stream`](Self::from_stream), then requests every known track
+    /// again in the original order, so the tracker assigns the same track indices.
+    /// The old keys are kept until the tracker sends new keys for the track, which replace all of the old keys.
+    /// If the tracker has no keys for a track, the old keys are kept, see the limitations of
+    /// [`reconnect`](RocketClient::reconnect).
+    ///
+    /// The next call to [`poll_events`](Self::poll_events) returns [`Event::Reconnected`].
+    ///
+    /// # Errors
+    ///
+    /// [`Error::Handshake`] if the handshake fails, or [`Error::IOError`] if sending the requests fails.
+    /// The client is left unchanged on error.
+    pub fn reconnect_with(&mut self, stream: S) -> Result<(), Error> {
+        let mut stream = stream;
+        handshake(&mut stream)?;
+        self.send_refresh(&mut stream)?;
+        self.replace_stream(stream);
+        Ok(())
+    }
+
+    /// Request every known track from the tracker on the other end of `stream`.
+    fn send_refresh(&self, stream: &mut S) -> Result<(), Error> {
+        let mut buf = Vec::new();
+        self.state.encode_refresh(&mut buf);
+        stream.write_all(&buf).map_err(Error::IOError)
+    }
+
+    fn replace_stream(&mut self, stream: S) {
+        self.stream = stream;
+        self.decoder.clear();
+        // Queued GET_TRACK messages are part of the refresh, and partially written messages are lost
+        self.send_queue.clear();
+        self.state.mark_stale();
+        self.reconnected = true;
+    }
+
+    /// Get a reference to the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Get a mutable reference to the underlying stream.
+    ///
+    /// Reading from or writing to the stream directly will corrupt the connection.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
     }
 
     /// Get track by name.
@@ -239,6 +760,7 @@ impl RocketClient {
     /// # Errors
     ///
     /// This method can return an [`Error::IOError`] if Rocket tracker disconnects.
+    /// If the request can't be sent right away, it is queued, see [`flush`](Self::flush).
     ///
     /// # Panics
     ///
@@ -246,38 +768,54 @@ impl RocketClient {
     ///
     /// # Examples
     ///
-    /// ```rust,no_run
-    /// # use rust_rocket::RocketClient;
-    /// # let mut rocket = RocketClient::new()?;
+    /// ```rust
+    /// # use rust_rocket::{server::MockTracker, RocketClient};
+    /// # let (_tracker, stream) = MockTracker::duplex();
+    /// # let mut rocket = RocketClient::from_stream(stream)?;
     /// let track = rocket.get_track_mut("namespace:track")?;
     /// track.get_value(3.5);
     /// # Ok::<(), rust_rocket::client::Error>(())
     /// ```
     pub fn get_track_mut(&mut self, name: &str) -> Result<&mut Track, Error> {
-        if let Some((i, _)) = self
-            .tracks
-            .iter()
-            .enumerate()
-            .find(|(_, t)| t.get_name() == name)
-        {
-            Ok(&mut self.tracks[i])
+        let id = self.get_track_id(name)?;
+        Ok(self.state.track_mut(id.index()))
+    }
+
+    /// Get a handle to a track by name, for fast lookups with [`get_track_by_id`](Self::get_track_by_id).
+    ///
+    /// If the track does not yet exist it will be created, like with [`get_track_mut`](Self::get_track_mut).
+    /// The handle stays valid across reconnects.
+    ///
+    /// # Errors
+    ///
+    /// This method can return an [`Error::IOError`] if Rocket tracker disconnects.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `name`'s length exceeds [`u32::MAX`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use rust_rocket::{server::MockTracker, RocketClient};
+    /// # let (_tracker, stream) = MockTracker::duplex();
+    /// # let mut rocket = RocketClient::from_stream(stream)?;
+    /// // During initialization
+    /// let camera_x = rocket.get_track_id("camera:x")?;
+    ///
+    /// // Every frame
+    /// let value = rocket.get_track_by_id(camera_x).unwrap().get_value(3.5);
+    /// # Ok::<(), rust_rocket::client::Error>(())
+    /// ```
+    pub fn get_track_id(&mut self, name: &str) -> Result<TrackId, Error> {
+        if let Some(id) = self.state.existing_track_id(name) {
+            Ok(id)
         } else {
             // Send GET_TRACK message
-            let mut buf = [GET_TRACK; 1 + GET_TRACK_LEN];
-            let name_len = u32::try_from(name.len()).expect("Track name too long");
-            BigEndian::write_u32(&mut buf[1..][..GET_TRACK_LEN], name_len);
-            self.stream.write_all(&buf).map_err(Error::IOError)?;
-            self.stream
-                .write_all(name.as_bytes())
-                .map_err(Error::IOError)?;
-
-            self.tracks.push(Track::new(name));
-            // SAFETY: tracks cannot be empty, because it was pushed to on the previous line
-            let track = self
-                .tracks
-                .last_mut()
-                .unwrap_or_else(|| unsafe { unreachable_unchecked() });
-            Ok(track)
+            protocol::encode_get_track(&mut self.send_queue, name);
+            let id = self.state.push(name);
+            self.flush()?;
+            Ok(id)
         }
     }
 
@@ -285,72 +823,152 @@ impl RocketClient {
     ///
     /// You should use [`get_track_mut`](RocketClient::get_track_mut) to create a track.
     pub fn get_track(&self, name: &str) -> Option<&Track> {
-        self.tracks.iter().find(|t| t.get_name() == name)
+        self.state.get_track(name)
+    }
+
+    /// Get track by a handle from [`get_track_id`](Self::get_track_id).
+    ///
+    /// Returns `None` if the handle is from another client or player with more tracks.
+    pub fn get_track_by_id(&self, id: TrackId) -> Option<&Track> {
+        self.state.get_track_by_id(id)
     }
 
     /// Get a snapshot of the tracks in the session.
     ///
-    /// The returned [`Tracks`] can be dumped to a file in any [supported format](crate#features).
-    /// The counterpart to this function is [`RocketPlayer::new`](crate::RocketPlayer::new),
+    /// The returned [`Tracks`] can be written to a [track file](crate::file), or dumped in any
+    /// [supported format](crate#features).
+    /// The counterpart to this function is [`RocketPlayer::load`](crate::RocketPlayer::load),
     /// which loads tracks for playback.
     ///
     /// # Example
     ///
     /// ```rust,no_run
-    /// # use rust_rocket::RocketClient;
-    /// # use std::fs::OpenOptions;
+    /// # use rust_rocket::{file::{self, Metadata}, RocketClient};
+    /// # use std::fs::File;
     /// let mut rocket = RocketClient::new()?;
     ///
     /// // Create tracks, call poll_events, etc...
     ///
-    /// // Open a file for writing
-    /// let mut file = OpenOptions::new()
-    ///     .write(true)
-    ///     .create(true)
-    ///     .truncate(true)
-    ///     .open("tracks.bin")
-    ///     .expect("Failed to open tracks.bin for writing");
-    ///
     /// // Save a snapshot of the client to a file for playback in release builds
     /// let tracks = rocket.save_tracks();
-    /// # #[cfg(feature = "bincode")]
-    /// bincode::encode_into_std_write(tracks, &mut file, bincode::config::standard())
-    ///     .expect("Failed to encode tracks.bin");
+    /// let file = File::create("tracks.bin").expect("Failed to create tracks.bin");
+    /// file::write(file, tracks, Metadata::new(120., 8)).expect("Failed to write tracks.bin");
     /// # Ok::<(), rust_rocket::client::Error>(())
     /// ```
     pub fn save_tracks(&self) -> &Tracks {
-        &self.tracks
+        self.state.tracks()
+    }
+
+    pub(crate) fn generation(&self) -> u64 {
+        self.state.generation()
+    }
+
+    /// Report tracks that were never read, or are empty or constant, see [`audit`](crate::audit).
+    ///
+    /// The client creates tracks when they're requested, so none are missing. To find stale tracks in a saved file,
+    /// use [`AuditReport::new`] with the tracks of the file and the names of this client's tracks.
+    pub fn audit(&self) -> AuditReport {
+        self.state.audit()
+    }
+
+    /// Get a counter that changes whenever the tracker sets or deletes a key in the named track.
+    ///
+    /// Compare it to a previously stored value to find out if data derived from the track needs to be rebuilt.
+    /// Returns `None` if the track hasn't been created with [`get_track_mut`](Self::get_track_mut).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use rust_rocket::{server::MockTracker, RocketClient};
+    /// # let (_tracker, stream) = MockTracker::duplex();
+    /// # let mut rocket = RocketClient::from_stream(stream)?;
+    /// rocket.get_track_mut("camera:path")?;
+    /// let mut baked = None;
+    ///
+    /// // Every frame
+    /// while let Some(_event) = rocket.poll_events()? {}
+    /// let generation = rocket.get_track_generation("camera:path");
+    /// if baked != generation {
+    ///     baked = generation;
+    ///     // Rebuild the camera spline...
+    /// }
+    /// # Ok::<(), rust_rocket::client::Error>(())
+    /// ```
+    pub fn get_track_generation(&self, name: &str) -> Option<u64> {
+        self.state.track_generation(name)
+    }
+
+    /// Enable or disable [`Event::KeySet`] and [`Event::KeyDeleted`] events. They are disabled by default.
+    ///
+    /// Keys are applied to the tracks whether or not the events are enabled.
+    pub fn set_key_events(&mut self, enabled: bool) {
+        self.state.set_key_events(enabled);
     }
 
     /// Send a SetRow message.
     ///
     /// This changes the current row on the tracker side.
+    /// If the message can't be sent right away, it is queued, see [`flush`](Self::flush).
+    /// Only the latest queued row is sent.
     ///
     /// # Errors
     ///
     /// This method can return an [`Error::IOError`] if Rocket tracker disconnects.
     pub fn set_row(&mut self, row: u32) -> Result<(), Error> {
-        // Send SET_ROW message
-        let mut buf = [SET_ROW; 1 + SET_ROW_LEN];
-        BigEndian::write_u32(&mut buf[1..][..SET_ROW_LEN], row);
-        self.stream.write_all(&buf).map_err(Error::IOError)
+        self.pending_row = Some(row);
+        self.flush()
+    }
+
+    /// Write as many queued messages to the tracker as possible without blocking.
+    ///
+    /// Messages are queued when the stream returns [`io::ErrorKind::WouldBlock`], e.g. when the socket's send buffer
+    /// is full. This is called by [`poll_events`](Self::poll_events), so you don't usually need to call it yourself.
+    ///
+    /// # Errors
+    ///
+    /// This method can return an [`Error::IOError`] if Rocket tracker disconnects.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        loop {
+            if self.send_queue.is_empty() {
+                match self.pending_row.take() {
+                    // Send SET_ROW message
+                    Some(row) => protocol::encode_set_row(&mut self.send_queue, row),
+                    None => return Ok(()),
+                }
+            }
+            match self.stream.write(&self.send_queue) {
+                Ok(0) => return Err(Error::IOError(io::ErrorKind::WriteZero.into())),
+                Ok(bytes_written) => {
+                    self.send_queue.drain(..bytes_written);
+                }
+                Err(e) => match e.kind() {
+                    io::ErrorKind::WouldBlock => return Ok(()),
+                    io::ErrorKind::Interrupted => { /* Retry */ }
+                    _ => return Err(Error::IOError(e)),
+                },
+            }
+        }
     }
 
     /// Poll for new events from the tracker.
     ///
-    /// This polls from events from the tracker.
+    /// This polls from events from the tracker, and sends queued messages (see [`flush`](Self::flush)).
     /// You should call this fairly often your main loop.
     /// It is recommended to keep calling this as long as your receive `Some(Event)`.
     ///
     /// # Errors
     ///
-    /// This method can return an [`Error::IOError`] if the rocket tracker disconnects.
+    /// This method can return an [`Error::IOError`] if the rocket tracker disconnects,
+    /// or a protocol error if the tracker sends malformed data.
+    /// After an error for which [`Error::is_recoverable`] returns `true`, you can keep polling.
     ///
     /// # Examples
     ///
-    /// ```rust,no_run
-    /// # use rust_rocket::RocketClient;
-    /// # let mut rocket = RocketClient::new()?;
+    /// ```rust
+    /// # use rust_rocket::{server::MockTracker, RocketClient};
+    /// # let (mut tracker, stream) = MockTracker::duplex();
+    /// # let mut rocket = RocketClient::from_stream(stream)?;
+    /// # tracker.set_row(1).unwrap();
     /// while let Some(event) = rocket.poll_events()? {
     ///     match event {
     ///         // Do something with the various events.
@@ -360,124 +978,510 @@ impl RocketClient {
     /// # Ok::<(), rust_rocket::client::Error>(())
     /// ```
     pub fn poll_events(&mut self) -> Result<Option<Event>, Error> {
+        if self.reconnected {
+            self.reconnected = false;
+            return Ok(Some(Event::Reconnected));
+        }
+        self.flush()?;
         loop {
-            match self.poll_event()? {
-                ReceiveResult::None => return Ok(None),
-                ReceiveResult::Incomplete => { /* Keep reading */ }
-                ReceiveResult::Some(event) => return Ok(Some(event)),
+            match self.decoder.decode()? {
+                Some(command) => {
+                    if let Some(event) = self.state.process_command(command)? {
+                        return Ok(Some(event));
+                    }
+                }
+                None => {
+                    if !self.receive()? {
+                        return Ok(None);
+                    }
+                }
             }
         }
     }
 
-    fn poll_event(&mut self) -> Result<ReceiveResult, Error> {
-        match self.state {
-            ClientState::New => self.poll_event_new(),
-            ClientState::Incomplete(bytes) => self.poll_event_incomplete(bytes),
-            ClientState::Complete => Ok(self.process_event().unwrap_or_else(|_| unreachable!())),
+    /// Poll for all events that are available from the tracker.
+    ///
+    /// This drains the connection, applying every key change, and appends the events to `events`.
+    /// Call it once per frame instead of calling [`poll_events`](Self::poll_events) in a loop.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`poll_events`](Self::poll_events). Events received before the error are still appended to `events`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use rust_rocket::{client::Event, server::MockTracker, RocketClient};
+    /// # let (mut tracker, stream) = MockTracker::duplex();
+    /// # let mut rocket = RocketClient::from_stream(stream)?;
+    /// # tracker.set_row(1).unwrap();
+    /// let mut events = Vec::new();
+    ///
+    /// // Every frame
+    /// rocket.poll_all(&mut events)?;
+    /// for event in events.drain(..) {
+    ///     match event {
+    ///         Event::SetRow(row) => println!("Seek to row {}", row),
+    ///         _ => (),
+    ///     }
+    /// }
+    /// # Ok::<(), rust_rocket::client::Error>(())
+    /// ```
+    pub fn poll_all(&mut self, events: &mut Vec<Event>) -> Result<(), Error> {
+        while let Some(event) = self.poll_events()? {
+            events.push(event);
         }
+        Ok(())
     }
 
-    fn poll_event_new(&mut self) -> Result<ReceiveResult, Error> {
-        let mut buf = [0; 1];
-        match self.stream.read_exact(&mut buf) {
-            Ok(()) => {
-                self.cmd.extend_from_slice(&buf);
-                match self.cmd[0] {
-                    SET_KEY => self.state = ClientState::Incomplete(SET_KEY_LEN),
-                    DELETE_KEY => self.state = ClientState::Incomplete(DELETE_KEY_LEN),
-                    SET_ROW => self.state = ClientState::Incomplete(SET_ROW_LEN),
-                    PAUSE => self.state = ClientState::Incomplete(PAUSE_LEN),
-                    SAVE_TRACKS => self.state = ClientState::Complete,
-                    _ => self.state = ClientState::Complete, // Error / Unknown
+    /// Read available bytes from the stream into the decoder.
+    ///
+    /// Returns `false` if no bytes were available.
+    fn receive(&mut self) -> Result<bool, Error> {
+        loop {
+            match self.stream.read(&mut self.receive_buf) {
+                Ok(0) => return Err(Error::IOError(io::ErrorKind::UnexpectedEof.into())),
+                Ok(bytes_read) => {
+                    self.decoder.feed(&self.receive_buf[..bytes_read]);
+                    return Ok(true);
                 }
-                Ok(ReceiveResult::Incomplete)
+                Err(e) => match e.kind() {
+                    io::ErrorKind::WouldBlock => return Ok(false),
+                    io::ErrorKind::Interrupted => { /* Retry */ }
+                    _ => return Err(Error::IOError(e)),
+                },
             }
-            Err(e) => match e.kind() {
-                std::io::ErrorKind::WouldBlock => Ok(ReceiveResult::None),
-                _ => Err(Error::IOError(e)),
-            },
         }
     }
+}
 
-    fn poll_event_incomplete(&mut self, bytes: usize) -> Result<ReceiveResult, Error> {
-        let mut buf = [0; MAX_COMMAND_LEN];
-        match self.stream.read(&mut buf[..bytes]) {
-            Ok(bytes_read) => {
-                self.cmd.extend_from_slice(&buf[..bytes_read]);
-                if bytes - bytes_read > 0 {
-                    self.state = ClientState::Incomplete(bytes - bytes_read);
-                } else {
-                    self.state = ClientState::Complete;
-                }
-                Ok(ReceiveResult::Incomplete)
+fn handshake<S: Read + Write>(stream: &mut S) -> Result<(), Error> {
+    stream
+        .write_all(CLIENT_GREETING)
+        .map_err(Error::Handshake)?;
+
+    let mut handshake = Handshake::new();
+    let mut buf = [0; SERVER_GREETING.len()];
+    while !handshake.check()? {
+        let buf = &mut buf[..handshake.remaining()];
+        stream.read_exact(buf).map_err(Error::Handshake)?;
+        handshake.feed(buf);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpolation::Interpolation;
+    use std::collections::VecDeque;
+
+    /// An in-memory stream. Reads return `WouldBlock` when `input` is empty,
+    /// and writes return `WouldBlock` when `output` is full.
+    #[derive(Debug, Default)]
+    struct MemoryStream {
+        input: VecDeque<u8>,
+        output: Vec<u8>,
+        output_limit: Option<usize>,
+        write_zero: bool,
+        reads: usize,
+    }
+
+    impl Read for MemoryStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.reads += 1;
+            if self.input.is_empty() {
+                return Err(io::ErrorKind::WouldBlock.into());
             }
-            Err(e) => match e.kind() {
-                std::io::ErrorKind::WouldBlock => Ok(ReceiveResult::None),
-                _ => Err(Error::IOError(e)),
-            },
+            let (front, _) = self.input.as_slices();
+            let len = front.len().min(buf.len());
+            buf[..len].copy_from_slice(&front[..len]);
+            self.input.drain(..len);
+            Ok(len)
         }
     }
 
-    // This function should never fail if [`poll_event_new`] and [`poll_event_incomplete`] are correct
-    fn process_event(&mut self) -> Result<ReceiveResult, io::Error> {
-        let mut result = ReceiveResult::None;
-
-        let mut cursor = Cursor::new(&self.cmd);
-        let cmd = cursor.read_u8()?;
-        match cmd {
-            SET_KEY => {
-                // usize::try_from(u32) will only be None if usize is smaller, and
-                // more than usize::MAX tracks are in use. That isn't possible because
-                // I'd imagine Vec::push and everything else will panic first.
-                // If you're running this on a microcontroller, I'd love to see it!
-                let index = usize::try_from(cursor.read_u32::<BigEndian>()?).unwrap();
-                let track = &mut self.tracks[index];
-                let row = cursor.read_u32::<BigEndian>()?;
-                let value = cursor.read_f32::<BigEndian>()?;
-                let interpolation = Interpolation::from(cursor.read_u8()?);
-                let key = Key::new(row, value, interpolation);
-
-                track.set_key(key);
+    impl Write for MemoryStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.write_zero {
+                return Ok(0);
             }
-            DELETE_KEY => {
-                let index = usize::try_from(cursor.read_u32::<BigEndian>()?).unwrap();
-                let track = &mut self.tracks[index];
-                let row = cursor.read_u32::<BigEndian>()?;
+            let space = match self.output_limit {
+                Some(limit) if self.output.len() >= limit => {
+                    return Err(io::ErrorKind::WouldBlock.into())
+                }
+                Some(limit) => limit - self.output.len(),
+                None => buf.len(),
+            };
+            self.output.write(&buf[..buf.len().min(space)])
+        }
 
-                track.delete_key(row);
-            }
-            SET_ROW => {
-                let row = cursor.read_u32::<BigEndian>()?;
-                result = ReceiveResult::Some(Event::SetRow(row));
-            }
-            PAUSE => {
-                let flag = cursor.read_u8()? == 1;
-                result = ReceiveResult::Some(Event::Pause(flag));
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn connected_client() -> RocketClient<MemoryStream> {
+        let mut stream = MemoryStream::default();
+        stream.input.extend(SERVER_GREETING);
+        let mut rocket = RocketClient::from_stream(stream).unwrap();
+        assert_eq!(rocket.get_ref().output, CLIENT_GREETING);
+        rocket.get_mut().output.clear();
+        rocket
+    }
+
+    fn send(rocket: &mut RocketClient<MemoryStream>, command: Command) {
+        let mut buf = Vec::new();
+        command.encode(&mut buf);
+        rocket.get_mut().input.extend(buf);
+    }
+
+    #[test]
+    fn greeting_mismatch() {
+        let mut stream = MemoryStream::default();
+        stream.input.extend(b"hello, world");
+        assert!(matches!(
+            RocketClient::from_stream(stream),
+            Err(Error::HandshakeGreetingMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn get_track_and_set_row() {
+        let mut rocket = connected_client();
+        rocket.get_track_mut("a").unwrap();
+        rocket.get_track_mut("a").unwrap();
+        rocket.set_row(1).unwrap();
+
+        let mut expected = Vec::new();
+        protocol::encode_get_track(&mut expected, "a");
+        protocol::encode_set_row(&mut expected, 1);
+        assert_eq!(rocket.get_ref().output, expected);
+    }
+
+    #[test]
+    fn audit() {
+        let mut rocket = connected_client();
+        let a = rocket.get_track_id("a").unwrap();
+        rocket.get_track_id("b").unwrap();
+        rocket.get_track_mut("c").unwrap();
+        for row in [4, 8] {
+            let key = Key::new(row, 2.0, Interpolation::Linear);
+            send(&mut rocket, Command::SetKey { track: 2, key });
+        }
+        while rocket.poll_events().unwrap().is_some() {}
+
+        assert_eq!(rocket.audit().get_unused_tracks(), ["a", "b", "c"]);
+
+        rocket.get_track_by_id(a);
+        rocket.get_track("c");
+        rocket.get_track("missing");
+        let report = rocket.audit();
+        assert_eq!(report.get_unused_tracks(), ["b"]);
+        assert_eq!(report.get_empty_tracks(), ["a"]);
+        assert_eq!(report.get_constant_tracks(), ["c"]);
+        assert!(report.get_missing_tracks().is_empty());
+    }
+
+    #[test]
+    fn documented_loop_reads_tracks() {
+        let mut rocket = connected_client();
+        for frame in 0..2 {
+            // The `get` helper of the module documentation
+            rocket.get_track_mut("a").unwrap().get_value(frame as f32);
+            rocket.get_track_mut("b").unwrap().get_value(frame as f32);
+        }
+        assert!(rocket.audit().get_unused_tracks().is_empty());
+    }
+
+    #[test]
+    fn commands_update_tracks() {
+        let mut rocket = connected_client();
+        rocket.get_track_mut("a").unwrap();
+        rocket.get_track_mut("b").unwrap();
+
+        let key = Key::new(4, 2.0, Interpolation::Linear);
+        send(&mut rocket, Command::SetKey { track: 1, key });
+        send(&mut rocket, Command::SetRow(8));
+        assert!(matches!(rocket.poll_events(), Ok(Some(Event::SetRow(8)))));
+        assert!(matches!(rocket.poll_events(), Ok(None)));
+        assert_eq!(rocket.get_track("b").unwrap().key_at(4), Some(&key));
+        assert!(rocket.get_track("a").unwrap().keys().is_empty());
+
+        send(&mut rocket, Command::DeleteKey { track: 1, row: 4 });
+        send(&mut rocket, Command::SaveTracks);
+        assert!(matches!(rocket.poll_events(), Ok(Some(Event::SaveTracks))));
+        assert!(rocket.get_track("b").unwrap().keys().is_empty());
+    }
+
+    #[test]
+    fn reconnect_keeps_tracks() {
+        use crate::server::MockTracker;
+
+        let (mut tracker, stream) = MockTracker::duplex();
+        let mut rocket = RocketClient::from_stream(stream).unwrap();
+        rocket.get_track_mut("a").unwrap();
+        let b_id = rocket.get_track_id("b").unwrap();
+        tracker.poll().unwrap();
+        let old_key = Key::new(0, 1.0, Interpolation::Step);
+        tracker.set_key("a", old_key).unwrap();
+        tracker
+            .set_key("b", Key::new(1, 1.0, Interpolation::Step))
+            .unwrap();
+        tracker
+            .set_key("b", Key::new(2, 2.0, Interpolation::Step))
+            .unwrap();
+        assert!(rocket.poll_events().unwrap().is_none());
+
+        drop(tracker);
+        assert!(rocket.poll_events().is_err());
+
+        let (mut tracker, stream) = MockTracker::duplex();
+        let new_key = Key::new(3, 3.0, Interpolation::Linear);
+        let mut b = Track::new("b");
+        b.set_key(new_key);
+        tracker.insert_track(b);
+        rocket.reconnect_with(stream).unwrap();
+        assert!(matches!(rocket.poll_events(), Ok(Some(Event::Reconnected))));
+
+        tracker.poll().unwrap();
+        assert_eq!(tracker.requested_tracks(), ["a", "b"]);
+        assert!(rocket.poll_events().unwrap().is_none());
+        assert_eq!(rocket.get_track("a").unwrap().keys(), [old_key]);
+        assert_eq!(rocket.get_track("b").unwrap().keys(), [new_key]);
+        assert_eq!(rocket.get_track_id("b").unwrap(), b_id);
+        assert_eq!(rocket.get_track_by_id(b_id).unwrap().keys(), [new_key]);
+
+        // Edits after the refresh don't replace keys
+        tracker.set_key("b", old_key).unwrap();
+        assert!(rocket.poll_events().unwrap().is_none());
+        assert_eq!(rocket.get_track("b").unwrap().keys(), [old_key, new_key]);
+    }
+
+    #[test]
+    fn unknown_track_index() {
+        let mut rocket = connected_client();
+        rocket.get_track_mut("a").unwrap();
+        let key = Key::new(0, 1.0, Interpolation::Step);
+        send(&mut rocket, Command::SetKey { track: 1, key });
+        send(&mut rocket, Command::DeleteKey { track: 7, row: 0 });
+        send(&mut rocket, Command::SetRow(2));
+
+        let error = rocket.poll_events().unwrap_err();
+        assert!(matches!(error, Error::UnknownTrackIndex(1)));
+        assert!(error.is_recoverable());
+        assert!(matches!(
+            rocket.poll_events(),
+            Err(Error::UnknownTrackIndex(7))
+        ));
+        assert!(matches!(rocket.poll_events(), Ok(Some(Event::SetRow(2)))));
+        assert!(rocket.get_track("a").unwrap().keys().is_empty());
+    }
+
+    #[test]
+    fn malformed_commands() {
+        let mut rocket = connected_client();
+        rocket.get_track_mut("a").unwrap();
+        let mut buf = Vec::new();
+        Command::SetKey {
+            track: 0,
+            key: Key::new(0, 1.0, Interpolation::Step),
+        }
+        .encode(&mut buf);
+        buf[13] = 9;
+        rocket.get_mut().input.extend(buf);
+        send(&mut rocket, Command::Pause(true));
+
+        let error = rocket.poll_events().unwrap_err();
+        assert!(matches!(error, Error::InvalidInterpolation(9)));
+        assert!(error.is_recoverable());
+        assert!(matches!(rocket.poll_events(), Ok(Some(Event::Pause(true)))));
+        assert!(rocket.get_track("a").unwrap().keys().is_empty());
+
+        rocket.get_mut().input.extend([0xff, protocol::SAVE_TRACKS]);
+        let error = rocket.poll_events().unwrap_err();
+        assert!(matches!(error, Error::UnknownCommand(0xff)));
+        assert!(!error.is_recoverable());
+        assert!(matches!(rocket.poll_events(), Ok(None)));
+    }
+
+    #[test]
+    fn fuzz_random_input() {
+        // xorshift32, so the test is deterministic
+        let mut state = 0x2545_f491_u32;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        };
+
+        for _ in 0..500 {
+            let mut rocket = connected_client();
+            for i in 0..next() % 4 {
+                rocket.get_track_mut(&i.to_string()).unwrap();
             }
-            SAVE_TRACKS => {
-                result = ReceiveResult::Some(Event::SaveTracks);
+            for _ in 0..next() % 8 {
+                // Mostly valid command bytes with small track indices and rows
+                let len = (next() % 32) as usize;
+                let bytes: Vec<u8> = (0..len)
+                    .map(|_| match next() % 4 {
+                        0 => next() as u8,
+                        _ => (next() % 6) as u8,
+                    })
+                    .collect();
+                rocket.get_mut().input.extend(bytes);
+                loop {
+                    match rocket.poll_events() {
+                        Ok(Some(_)) => {}
+                        Ok(None) => break,
+                        Err(e) => assert!(!matches!(e, Error::IOError(_))),
+                    }
+                }
             }
-            _ => eprintln!("rocket: Unknown command: {:?}", cmd),
         }
+    }
+
+    #[test]
+    fn key_events_and_generations() {
+        let mut rocket = connected_client();
+        rocket.get_track_mut("a").unwrap();
+        rocket.get_track_mut("b").unwrap();
+        assert_eq!(rocket.get_track_generation("a"), Some(0));
+        assert_eq!(rocket.get_track_generation("c"), None);
+
+        let key = Key::new(4, 2.0, Interpolation::Linear);
+        send(&mut rocket, Command::SetKey { track: 1, key });
+        assert!(matches!(rocket.poll_events(), Ok(None)));
+        assert_eq!(rocket.get_track_generation("a"), Some(0));
+        assert_eq!(rocket.get_track_generation("b"), Some(1));
+
+        rocket.set_key_events(true);
+        send(&mut rocket, Command::SetKey { track: 0, key });
+        send(&mut rocket, Command::DeleteKey { track: 1, row: 4 });
+        assert!(matches!(
+            rocket.poll_events(),
+            Ok(Some(Event::KeySet { track: 0, key: k })) if k == key
+        ));
+        assert!(matches!(
+            rocket.poll_events(),
+            Ok(Some(Event::KeyDeleted { track: 1, row: 4 }))
+        ));
+        assert!(matches!(rocket.poll_events(), Ok(None)));
+        assert_eq!(rocket.get_track_generation("a"), Some(1));
+        assert_eq!(rocket.get_track_generation("b"), Some(2));
+    }
+
+    #[test]
+    fn batched_reads() {
+        let mut rocket = connected_client();
+        rocket.get_track_mut("a").unwrap();
+        for row in 0..10_000 {
+            let key = Key::new(row, row as f32, Interpolation::Step);
+            send(&mut rocket, Command::SetKey { track: 0, key });
+        }
+        send(&mut rocket, Command::Pause(false));
+        send(&mut rocket, Command::SetRow(5));
+        rocket.get_mut().reads = 0;
 
-        self.cmd.clear();
-        self.state = ClientState::New;
+        let mut events = Vec::new();
+        rocket.poll_all(&mut events).unwrap();
+        assert!(matches!(
+            events[..],
+            [Event::Pause(false), Event::SetRow(5)]
+        ));
+        assert_eq!(rocket.get_track("a").unwrap().keys().len(), 10_000);
+        // 140 kB of commands, then WouldBlock
+        assert!(
+            rocket.get_ref().reads <= 4,
+            "{} reads",
+            rocket.get_ref().reads
+        );
+    }
 
-        Ok(result)
+    #[test]
+    fn send_queue() {
+        let mut rocket = connected_client();
+        rocket.get_mut().output_limit = Some(3);
+        rocket.set_row(1).unwrap();
+        rocket.get_track_mut("a").unwrap();
+        rocket.set_row(2).unwrap();
+        rocket.set_row(3).unwrap();
+        assert!(matches!(rocket.poll_events(), Ok(None)));
+        assert_eq!(rocket.get_ref().output.len(), 3);
+
+        rocket.get_mut().output_limit = None;
+        assert!(matches!(rocket.poll_events(), Ok(None)));
+        let mut expected = Vec::new();
+        protocol::encode_set_row(&mut expected, 1);
+        protocol::encode_get_track(&mut expected, "a");
+        protocol::encode_set_row(&mut expected, 3);
+        assert_eq!(rocket.get_ref().output, expected);
     }
 
-    fn handshake(&mut self) -> Result<(), Error> {
-        self.stream
-            .write_all(CLIENT_GREETING)
-            .map_err(Error::Handshake)?;
+    #[test]
+    fn write_zero_is_an_error() {
+        let mut rocket = connected_client();
+        rocket.get_mut().output_limit = Some(0);
+        rocket.set_row(1).unwrap();
 
-        let mut buf = [0; SERVER_GREETING.len()];
-        self.stream.read_exact(&mut buf).map_err(Error::Handshake)?;
+        rocket.get_mut().write_zero = true;
+        assert!(matches!(rocket.poll_events(), Err(Error::IOError(_))));
+    }
 
-        if buf == SERVER_GREETING {
-            Ok(())
-        } else {
-            Err(Error::HandshakeGreetingMismatch(buf))
-        }
+    #[test]
+    fn connect_options() {
+        use crate::server::MockTracker;
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let tracker = std::thread::spawn(move || MockTracker::accept(&listener).unwrap());
+
+        let rocket = ConnectOptions::new()
+            .address(address)
+            .connect_timeout(Some(Duration::from_secs(5)))
+            .nodelay(true)
+            .connect()
+            .unwrap();
+        tracker.join().unwrap();
+        assert!(rocket.get_ref().nodelay().unwrap());
+        assert_eq!(rocket.get_ref().read_timeout().unwrap(), None);
+    }
+
+    #[test]
+    fn handshake_timeout() {
+        use std::net::TcpListener;
+
+        // Accepts connections in the backlog, but never answers
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let options = ConnectOptions::new().handshake_timeout(Some(Duration::from_millis(50)));
+        assert!(matches!(
+            options.address(addr.to_string()).connect(),
+            Err(Error::Timeout)
+        ));
+        assert!(matches!(
+            RocketClient::connect_timeout(addr, Duration::from_millis(50)),
+            Err(Error::Timeout)
+        ));
+    }
+
+    #[test]
+    fn handshake_failure_tries_next_address() {
+        use crate::server::MockTracker;
+        use std::net::TcpListener;
+
+        let silent = TcpListener::bind("127.0.0.1:0").unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addrs = [silent.local_addr().unwrap(), listener.local_addr().unwrap()];
+        let tracker = std::thread::spawn(move || MockTracker::accept(&listener).unwrap());
+
+        let options = ConnectOptions::new().handshake_timeout(Some(Duration::from_millis(50)));
+        let rocket = RocketClient::connect_with(&addrs[..], &options).unwrap();
+        tracker.join().unwrap();
+        assert_eq!(rocket.addr, Some(addrs[1]));
+        assert_eq!(rocket.get_ref().write_timeout().unwrap(), None);
     }
 }
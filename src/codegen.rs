@@ -0,0 +1,120 @@
+//! Generating source code from saved tracks, to compile sync data directly into a production.
+//!
+//! The [`rust`] module generates Rust source with the keys in static arrays and a matching `no_std` evaluator,
+//! so release builds don't need to load or decode a file, and carry no serialization code.
+//!
+//! The [`c`] module generates a C header with the keys in static arrays and a single-header evaluator,
+//! for C and C++ productions.
+//!
+//! The [`glsl`] and [`wgsl`] modules generate shader functions with the keys in constant tables,
+//! for productions that evaluate tracks on the GPU.
+//!
+//! Track names are turned into identifiers by replacing every character other than ASCII letters and digits with
+//! `_` and converting to uppercase, e.g. `camera:pos.x` becomes `CAMERA_POS_X`.
+//! Names that collide get a numbered suffix.
+
+use crate::track::Track;
+
+pub mod c;
+pub mod glsl;
+pub mod rust;
+mod shader;
+pub mod wgsl;
+
+/// Get a unique identifier for each track, in the order of `tracks`.
+///
+/// The generated code derives an identifier from each track's identifier with every one of `suffixes`.
+/// These must not be any of `reserved`, the identifiers used by the rest of the generated code,
+/// or the identifiers derived from another track.
+fn identifiers(tracks: &[Track], reserved: &[&str], suffixes: &[&str]) -> Vec<String> {
+    let mut identifiers: Vec<String> = Vec::with_capacity(tracks.len());
+    let mut used: Vec<String> = reserved.iter().map(|&name| String::from(name)).collect();
+    for track in tracks {
+        let base = identifier(track.get_name());
+        let mut candidate = base.clone();
+        let mut number = 2;
+        while suffixes
+            .iter()
+            .any(|suffix| used.contains(&format!("{}{}", candidate, suffix)))
+        {
+            candidate = format!("{}_{}", base, number);
+            number += 1;
+        }
+        used.extend(
+            suffixes
+                .iter()
+                .map(|suffix| format!("{}{}", candidate, suffix)),
+        );
+        identifiers.push(candidate);
+    }
+    identifiers
+}
+
+fn identifier(name: &str) -> String {
+    let mut identifier = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            identifier.push(c.to_ascii_uppercase());
+        } else if !identifier.is_empty() && !identifier.ends_with('_') {
+            identifier.push('_');
+        }
+    }
+    if identifier.ends_with('_') {
+        identifier.pop();
+    }
+
+    match identifier.chars().next() {
+        None => String::from("TRACK"),
+        Some(c) if c.is_ascii_digit() => format!("TRACK_{}", identifier),
+        Some(_) => identifier,
+    }
+}
+
+/// Format a finite float as a literal that parses back to the same value.
+///
+/// The literal is valid in Rust, C and the shader languages, but has no suffix. The generators spell values that
+/// aren't finite themselves.
+fn float_literal(value: f32) -> String {
+    debug_assert!(value.is_finite());
+    // Debug formatting is the shortest representation that round-trips, and always has a `.` or an exponent
+    format!("{:?}", value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unique_identifiers() {
+        let tracks: Vec<Track> = [
+            "camera:pos.x",
+            "camera::pos x",
+            "1st",
+            "::",
+            "",
+            "ä",
+            "TRACKS",
+        ]
+        .iter()
+        .map(|&name| Track::new(name))
+        .collect();
+        assert_eq!(
+            identifiers(&tracks, &["TRACKS"], &[""]),
+            [
+                "CAMERA_POS_X",
+                "CAMERA_POS_X_2",
+                "TRACK_1ST",
+                "TRACK",
+                "TRACK_2",
+                "TRACK_3",
+                "TRACKS_2"
+            ]
+        );
+
+        let tracks = [Track::new("a"), Track::new("a keys"), Track::new("b")];
+        assert_eq!(
+            identifiers(&tracks, &["B"], &["", "_KEYS"]),
+            ["A", "A_KEYS_2", "B_2"]
+        );
+    }
+}
@@ -0,0 +1,388 @@
+//! Generating C headers from saved tracks.
+//!
+//! The generated header is self-contained and works in C99 and C++ productions. It contains:
+//!
+//! - A single-header evaluator: `struct rocket_key`, `struct rocket_track` and `rocket_get_value`, which evaluates
+//!   tracks like [`Track::get_value`](crate::track::Track::get_value).
+//! - For every track, a packed key array `SYNC_<TRACK>_KEYS`, a `struct rocket_track` `SYNC_<TRACK>` and
+//!   a `SYNC_<TRACK>_NAME` constant with the track name, named after the track (see [`codegen`](super)).
+//! - `SYNC_NUM_TRACKS`, and `SYNC_TRACKS` and `SYNC_TRACK_NAMES` arrays in the order of the saved tracks.
+//!
+//! To use headers generated from different tracks together, e.g. one per scene, generate them with different
+//! prefixes instead of `SYNC_` with [`write_with_prefix`]. The include guard of the header is derived from the
+//! prefix, and the evaluator has its own include guard.
+//!
+//! The keys are packed like in the [track file](mod@crate::file), 9 bytes per key.
+//!
+//! The evaluator gives the same results as [`Track::get_value`](crate::track::Track::get_value) as long as the
+//! C compiler uses single precision arithmetic and doesn't fuse multiplications and additions.
+//! This is the default for x86-64 with MSVC and Clang. Pass `-ffp-contract=off` to GCC, which fuses them
+//! on platforms with fused multiply-add instructions, e.g. ARM.
+//!
+//! # Usage
+//!
+//! ```rust,no_run
+//! # use rust_rocket::{codegen, file};
+//! # use std::fs::File;
+//! let file = File::open("tracks.bin").expect("Failed to open tracks.bin");
+//! let tracks = file::read(file).expect("Failed to read tracks.bin").into_tracks();
+//!
+//! let header = File::create("sync.h").expect("Failed to create sync.h");
+//! codegen::c::write(header, &tracks).expect("Failed to write sync.h");
+//! ```
+//!
+//! And in the production:
+//!
+//! ```c
+//! #include "sync.h"
+//!
+//! float fov = rocket_get_value(&SYNC_CAMERA_FOV, row);
+//! ```
+
+use crate::track::Track;
+use std::io::{self, Write};
+
+/// The evaluator that is copied into the generated header.
+const RUNTIME: &str = include_str!("c_runtime.h");
+
+/// The prefix of the identifiers generated by [`write`](fn@write).
+const DEFAULT_PREFIX: &str = "SYNC_";
+
+/// The evaluator's constants for each [`Interpolation`](crate::interpolation::Interpolation), by value.
+const INTERPOLATIONS: [&str; 4] = [
+    "ROCKET_STEP",
+    "ROCKET_LINEAR",
+    "ROCKET_SMOOTH",
+    "ROCKET_RAMP",
+];
+
+/// Identifiers of the generated header that tracks can't use, without the prefix.
+const RESERVED: &[&str] = &["NUM_TRACKS", "TRACKS", "TRACK_NAMES"];
+
+/// Suffixes of the identifiers generated for each track.
+const SUFFIXES: &[&str] = &["", "_KEYS", "_NAME"];
+
+/// Write tracks as a C header, with identifiers prefixed by `SYNC_`.
+///
+/// # Errors
+///
+/// Any error from writing to `writer`.
+pub fn write<W: Write>(writer: W, tracks: &[Track]) -> io::Result<()> {
+    write_with_prefix(writer, tracks, DEFAULT_PREFIX)
+}
+
+/// Write tracks as a C header, with identifiers prefixed by `prefix` instead of `SYNC_`.
+///
+/// The include guard is `RUST_ROCKET_<PREFIX>_H`, e.g. `RUST_ROCKET_INTRO_H` for the prefix `INTRO_`.
+///
+/// # Errors
+///
+/// Any error from writing to `writer`.
+///
+/// # Panics
+///
+/// If `prefix` isn't the start of a C identifier: ASCII letters, digits and `_`, not starting with a digit.
+pub fn write_with_prefix<W: Write>(
+    mut writer: W,
+    tracks: &[Track],
+    prefix: &str,
+) -> io::Result<()> {
+    assert!(
+        prefix.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && prefix
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        "Invalid prefix for C identifiers: {:?}",
+        prefix
+    );
+    let guard = format!("RUST_ROCKET_{}_H", prefix.trim_end_matches('_'));
+
+    writeln!(
+        writer,
+        "/* Generated by rust-rocket from {} tracks. Do not edit. */",
+        tracks.len()
+    )?;
+    writeln!(writer, "#ifndef {}", guard)?;
+    writeln!(writer, "#define {}", guard)?;
+    writeln!(writer)?;
+    writer.write_all(RUNTIME.as_bytes())?;
+
+    let non_finite = tracks
+        .iter()
+        .flat_map(Track::keys)
+        .any(|key| !key.get_value().is_finite());
+    if non_finite {
+        writeln!(writer)?;
+        writeln!(writer, "#include <math.h>")?;
+    }
+
+    let identifiers: Vec<String> = super::identifiers(tracks, RESERVED, SUFFIXES)
+        .into_iter()
+        .map(|identifier| format!("{}{}", prefix, identifier))
+        .collect();
+    for (track, identifier) in tracks.iter().zip(&identifiers) {
+        writeln!(writer)?;
+        writeln!(
+            writer,
+            "#define {}_NAME {}",
+            identifier,
+            string_literal(track.get_name())
+        )?;
+        if track.keys().is_empty() {
+            writeln!(
+                writer,
+                "static const struct rocket_track {} = {{NULL, 0}};",
+                identifier
+            )?;
+            continue;
+        }
+        writeln!(
+            writer,
+            "static const struct rocket_key {}_KEYS[] = {{",
+            identifier
+        )?;
+        for key in track.keys() {
+            writeln!(
+                writer,
+                "    {{{}u, {}, {}}},",
+                key.get_row(),
+                float_literal(key.get_value()),
+                INTERPOLATIONS[key.get_interpolation() as usize]
+            )?;
+        }
+        writeln!(writer, "}};")?;
+        writeln!(
+            writer,
+            "static const struct rocket_track {0} = {{{0}_KEYS, {1}}};",
+            identifier,
+            track.keys().len()
+        )?;
+    }
+
+    writeln!(writer)?;
+    writeln!(writer, "#define {}NUM_TRACKS {}", prefix, tracks.len())?;
+    if !tracks.is_empty() {
+        write!(
+            writer,
+            "static const char *const {0}TRACK_NAMES[{0}NUM_TRACKS] = {{",
+            prefix
+        )?;
+        for identifier in &identifiers {
+            write!(writer, "\n    {}_NAME,", identifier)?;
+        }
+        writeln!(writer, "\n}};")?;
+        write!(
+            writer,
+            "static const struct rocket_track *const {0}TRACKS[{0}NUM_TRACKS] = {{",
+            prefix
+        )?;
+        for identifier in &identifiers {
+            write!(writer, "\n    &{},", identifier)?;
+        }
+        writeln!(writer, "\n}};")?;
+    }
+
+    writeln!(writer)?;
+    writeln!(writer, "#endif /* {} */", guard)
+}
+
+/// Write tracks as a C header into a `String`, see [`write`](fn@write).
+pub fn to_string(tracks: &[Track]) -> String {
+    to_string_with_prefix(tracks, DEFAULT_PREFIX)
+}
+
+/// Write tracks as a C header into a `String`, see [`write_with_prefix`].
+///
+/// # Panics
+///
+/// See [`write_with_prefix`].
+pub fn to_string_with_prefix(tracks: &[Track], prefix: &str) -> String {
+    let mut buf = Vec::new();
+    // Writing to a Vec can't fail
+    write_with_prefix(&mut buf, tracks, prefix).unwrap();
+    // Track names are escaped to ASCII and everything else is ASCII
+    String::from_utf8(buf).unwrap()
+}
+
+/// Format a float as a C `float` literal that parses back to the same value.
+fn float_literal(value: f32) -> String {
+    if value.is_nan() {
+        String::from("NAN")
+    } else if value == f32::INFINITY {
+        String::from("INFINITY")
+    } else if value == f32::NEG_INFINITY {
+        String::from("-INFINITY")
+    } else {
+        format!("{}f", super::float_literal(value))
+    }
+}
+
+/// Format a string as an ASCII C string literal.
+fn string_literal(value: &str) -> String {
+    let mut literal = String::with_capacity(value.len() + 2);
+    literal.push('"');
+    for &byte in value.as_bytes() {
+        match byte {
+            // `?` is escaped to avoid trigraphs
+            b'"' | b'\\' | b'?' => {
+                literal.push('\\');
+                literal.push(char::from(byte));
+            }
+            b' '..=b'~' => literal.push(char::from(byte)),
+            // Octal escapes have at most three digits, unlike hexadecimal escapes
+            _ => literal.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpolation::Interpolation;
+    use crate::track::{tests::sample_project, Key};
+    use std::{fs, process::Command};
+
+    /// The shared sample project, and tracks with names and values that need escaping in C.
+    fn c_project() -> Vec<Track> {
+        let mut tracks = sample_project();
+        let mut flash = Track::new("fx:flash \"?\" ä");
+        for i in (0..32u32).step_by(5) {
+            flash.set_key(Key::new(i * 11, 1. / (i as f32 + 1.), Interpolation::Ramp));
+        }
+        let mut special = Track::new("special");
+        special.set_key(Key::new(2, -0.0, Interpolation::Linear));
+        special.set_key(Key::new(4, 1e-40, Interpolation::Linear));
+        special.set_key(Key::new(6, 3.4e38, Interpolation::Step));
+        special.set_key(Key::new(8, f32::NEG_INFINITY, Interpolation::Step));
+        tracks.extend([flash, special]);
+        tracks
+    }
+
+    /// Every row and half row of the project, and rows outside of it.
+    fn sample_rows(tracks: &[Track]) -> Vec<f32> {
+        let last_row = tracks
+            .iter()
+            .filter_map(|track| track.keys().last())
+            .map(|key| key.get_row())
+            .max()
+            .unwrap_or(0);
+        let mut rows: Vec<f32> = (0..=(last_row + 2) * 2)
+            .map(|row| row as f32 / 2.)
+            .collect();
+        rows.extend([-1.5, 0.25, 1e10, f32::INFINITY, f32::NAN]);
+        rows
+    }
+
+    #[test]
+    fn generated_header() {
+        let header = to_string(&c_project());
+        assert!(header.starts_with("/* Generated by rust-rocket from 6 tracks."));
+        assert!(header.contains("#define SYNC_CAMERA_X_NAME \"camera:x\"\n"));
+        assert!(header.contains("#define SYNC_FX_FLASH_NAME \"fx:flash \\\"\\?\\\" \\303\\244\"\n"));
+        assert!(header.contains("static const struct rocket_key SYNC_CAMERA_FOV_KEYS[] = {\n    {5u, 45.0f, ROCKET_SMOOTH},\n"));
+        assert!(header.contains("    {8u, -INFINITY, ROCKET_STEP},\n"));
+        assert!(header.contains("static const struct rocket_track SYNC_EMPTY = {NULL, 0};\n"));
+        assert!(header.contains("#define SYNC_NUM_TRACKS 6\n"));
+        assert!(header.ends_with("    &SYNC_SPECIAL,\n};\n\n#endif /* RUST_ROCKET_SYNC_H */\n"));
+
+        let empty = to_string(&[]);
+        assert!(empty.contains("#define SYNC_NUM_TRACKS 0\n"));
+        assert!(!empty.contains("SYNC_TRACKS"));
+
+        let intro = to_string_with_prefix(&sample_project(), "INTRO_");
+        assert!(intro.contains("#ifndef RUST_ROCKET_INTRO_H\n#define RUST_ROCKET_INTRO_H\n"));
+        assert!(intro.contains("static const struct rocket_track INTRO_EMPTY = {NULL, 0};\n"));
+        assert!(intro.contains("#define INTRO_NUM_TRACKS 4\n"));
+        assert!(!intro.contains("SYNC_"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid prefix")]
+    fn invalid_prefix() {
+        to_string_with_prefix(&[], "2ND_");
+    }
+
+    /// Compile the generated header into a program that prints the values of every track at every row of
+    /// the sample project, which must match the tracks. Skipped if no C compiler can be run.
+    #[test]
+    fn c_evaluator_matches_track() {
+        let tracks = c_project();
+        let rows = sample_rows(&tracks);
+
+        let mut program = String::from(
+            "#include <stdio.h>\n#include <string.h>\n#include <math.h>\n#include \"sync.h\"\n#include \"intro.h\"\n\n",
+        );
+        program.push_str("static const float rows[] = {\n");
+        for &row in &rows {
+            program.push_str(&format!("    {},\n", float_literal(row)));
+        }
+        program.push_str(
+            "};
+
+int main(void)
+{
+    size_t track, row;
+    if (INTRO_NUM_TRACKS != 1 || INTRO_TRACKS[0]->num_keys != SYNC_TRACKS[0]->num_keys
+        || strcmp(INTRO_TRACK_NAMES[0], SYNC_TRACK_NAMES[0]) != 0) {
+        return 1;
+    }
+    for (track = 0; track < SYNC_NUM_TRACKS; track++) {
+        for (row = 0; row < sizeof(rows) / sizeof(rows[0]); row++) {
+            float value = rocket_get_value(SYNC_TRACKS[track], rows[row]);
+            uint32_t bits;
+            memcpy(&bits, &value, sizeof(bits));
+            printf(\"%lu\\n\", (unsigned long)bits);
+        }
+    }
+    return 0;
+}
+",
+        );
+
+        let directory =
+            std::env::temp_dir().join(format!("rust-rocket-codegen-c-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("sync.h"), to_string(&tracks)).unwrap();
+        // A second header, which must not clash with the first
+        let intro = to_string_with_prefix(&tracks[..1], "INTRO_");
+        fs::write(directory.join("intro.h"), intro).unwrap();
+        fs::write(directory.join("main.c"), program).unwrap();
+        let compiled = Command::new("cc")
+            .args(["-std=c99", "-pedantic", "-Wall", "-Wextra", "-Werror"])
+            .args(["-ffp-contract=off", "-o", "main", "main.c"])
+            .current_dir(&directory)
+            .status();
+        match compiled {
+            Ok(status) => assert!(status.success(), "Generated header doesn't compile"),
+            Err(_) => {
+                fs::remove_dir_all(&directory).unwrap();
+                eprintln!("cc not available, skipping");
+                return;
+            }
+        }
+
+        let output = Command::new(directory.join("main")).output().unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+        let values: Vec<u32> = String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(|line| line.parse().unwrap())
+            .collect();
+        let expected: Vec<u32> = tracks
+            .iter()
+            .flat_map(|track| rows.iter().map(move |&row| track.get_value(row).to_bits()))
+            .collect();
+        assert_eq!(values.len(), expected.len());
+        for (i, (value, expected)) in values.iter().zip(&expected).enumerate() {
+            assert_eq!(
+                value,
+                expected,
+                "{} at row {}",
+                tracks[i / rows.len()].get_name(),
+                rows[i % rows.len()]
+            );
+        }
+    }
+}
@@ -0,0 +1,83 @@
+#ifndef RUST_ROCKET_EVALUATOR_H
+#define RUST_ROCKET_EVALUATOR_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+/* Interpolations, from a key to the next key */
+#define ROCKET_STEP 0
+#define ROCKET_LINEAR 1
+#define ROCKET_SMOOTH 2
+#define ROCKET_RAMP 3
+
+#pragma pack(push, 1)
+/* A key of a track */
+struct rocket_key {
+    uint32_t row;
+    float value;
+    uint8_t interpolation;
+};
+#pragma pack(pop)
+
+/* A track, its keys sorted by row */
+struct rocket_track {
+    const struct rocket_key *keys;
+    size_t num_keys;
+};
+
+/* Perform an interpolation, like rust_rocket::interpolation::Interpolation::interpolate */
+static inline float rocket_interpolate(uint8_t interpolation, float t)
+{
+    switch (interpolation) {
+    case ROCKET_LINEAR:
+        return t;
+    case ROCKET_SMOOTH:
+        return t * t * (3.0f - 2.0f * t);
+    case ROCKET_RAMP:
+        return t * t;
+    default:
+        return 0.0f;
+    }
+}
+
+/* Get a value of a track based on a row, like rust_rocket::track::Track::get_value */
+static inline float rocket_get_value(const struct rocket_track *track, float row)
+{
+    const struct rocket_key *keys = track->keys;
+    size_t lo = 0, hi = track->num_keys;
+    uint32_t lower_row;
+    const struct rocket_key *lower, *higher;
+    float t;
+
+    if (track->num_keys == 0)
+        return 0.0f;
+
+    /* Saturate like a Rust cast, converting out of range floats is undefined in C */
+    if (!(row >= 0.0f))
+        lower_row = 0;
+    else if (row >= 4294967296.0f)
+        lower_row = UINT32_MAX;
+    else
+        lower_row = (uint32_t)row;
+
+    if (lower_row <= keys[0].row)
+        return keys[0].value;
+    if (lower_row >= keys[track->num_keys - 1].row)
+        return keys[track->num_keys - 1].value;
+
+    /* Find the first key after lower_row */
+    while (lo < hi) {
+        size_t mid = lo + (hi - lo) / 2;
+        if (keys[mid].row <= lower_row)
+            lo = mid + 1;
+        else
+            hi = mid;
+    }
+    lower = &keys[lo - 1];
+    higher = &keys[lo];
+
+    t = (row - (float)lower->row) / ((float)higher->row - (float)lower->row);
+    return lower->value + (higher->value - lower->value) * rocket_interpolate(lower->interpolation, t);
+}
+
+#endif /* RUST_ROCKET_EVALUATOR_H */
@@ -0,0 +1,246 @@
+//! Generating GLSL functions from saved tracks.
+//!
+//! The generated code contains a `float sync_<track>(float row)` function for every track, named after the track
+//! (see [`codegen`](super)), e.g. `float sync_camera_x(float row)`.
+//! Each function evaluates a constant table of the track's keys like [`Track::get_value`], using only
+//! single precision arithmetic.
+//! The code has no `#version` directive, and works in GLSL 1.30 and later, and GLSL ES 3.00 and later.
+//!
+//! GPUs don't always round like the CPU, so values can differ in the last bits between a shader and
+//! [`Track::get_value`], depending on the GPU and the driver. A NaN row gives an unspecified value.
+//!
+//! # Usage
+//!
+//! ```rust,no_run
+//! # use rust_rocket::{codegen, file};
+//! # use std::fs::File;
+//! let file = File::open("tracks.bin").expect("Failed to open tracks.bin");
+//! let tracks = file::read(file).expect("Failed to read tracks.bin").into_tracks();
+//!
+//! let sync = codegen::glsl::to_string(&tracks).expect("Tracks can't be used in shaders");
+//! # let fragment = "";
+//! // let fragment = include_str!("shader.frag");
+//! let shader = format!("#version 330\n{}\n{}", sync, fragment);
+//! ```
+//!
+//! ```glsl
+//! // shader.frag
+//! uniform float row;
+//! out vec4 color;
+//!
+//! void main() {
+//!     color = vec4(sync_camera_x(row));
+//! }
+//! ```
+
+use super::shader::{self, PREFIX};
+use crate::track::Track;
+use std::io::{self, Write};
+
+/// The functions shared by every track, see [`shader`].
+const HELPERS: &str = "\
+float sync_interpolate(int interpolation, float t) {
+    if (interpolation == 1) return t;
+    if (interpolation == 2) return t * t * (3.0 - 2.0 * t);
+    if (interpolation == 3) return t * t;
+    return 0.0;
+}
+
+float sync_segment(float row, float lower_row, float higher_row, float lower_value, float higher_value,
+                   int interpolation) {
+    float t = (row - lower_row) / (higher_row - lower_row);
+    return lower_value + (higher_value - lower_value) * sync_interpolate(interpolation, t);
+}
+";
+
+/// Write tracks as GLSL functions.
+///
+/// # Errors
+///
+/// Any error from writing to `writer`, or [`io::ErrorKind::InvalidData`] if a key has a row of
+/// 2<sup>24</sup> or more, or a value that isn't finite. Shaders can't represent those exactly.
+pub fn write<W: Write>(mut writer: W, tracks: &[Track]) -> io::Result<()> {
+    let identifiers = shader::identifiers(tracks)?;
+
+    writeln!(
+        writer,
+        "// Generated by rust-rocket from {} tracks. Do not edit.",
+        tracks.len()
+    )?;
+    writeln!(writer)?;
+    writer.write_all(HELPERS.as_bytes())?;
+
+    for (track, name) in tracks.iter().zip(&identifiers) {
+        writeln!(writer)?;
+        let keys = track.keys();
+        let (first, last) = match keys {
+            [] => {
+                writeln!(writer, "float {}(float row) {{ return 0.0; }}", name)?;
+                continue;
+            }
+            [key] => {
+                let value = super::float_literal(key.get_value());
+                writeln!(writer, "float {}(float row) {{ return {}; }}", name, value)?;
+                continue;
+            }
+            [first, .., last] => (first, last),
+        };
+
+        let len = keys.len();
+        let rows: Vec<String> = keys
+            .iter()
+            .map(|key| shader::row_literal(key.get_row()))
+            .collect();
+        let values: Vec<String> = keys
+            .iter()
+            .map(|key| super::float_literal(key.get_value()))
+            .collect();
+        let interpolations: Vec<String> = keys
+            .iter()
+            .map(|key| (key.get_interpolation() as u8).to_string())
+            .collect();
+        writeln!(
+            writer,
+            "const float {}_rows[{}] = float[{1}]({});",
+            name,
+            len,
+            rows.join(", ")
+        )?;
+        writeln!(
+            writer,
+            "const float {}_values[{}] = float[{1}]({});",
+            name,
+            len,
+            values.join(", ")
+        )?;
+        writeln!(
+            writer,
+            "const int {}_interpolations[{}] = int[{1}]({});",
+            name,
+            len,
+            interpolations.join(", ")
+        )?;
+
+        let last_value = super::float_literal(last.get_value());
+        writeln!(writer)?;
+        writeln!(writer, "float {}(float row) {{", name)?;
+        writeln!(
+            writer,
+            "    if (row < {}) return {};",
+            shader::row_literal(first.get_row() + 1),
+            super::float_literal(first.get_value())
+        )?;
+        writeln!(
+            writer,
+            "    if (row >= {}) return {};",
+            shader::row_literal(last.get_row()),
+            last_value
+        )?;
+        writeln!(writer, "    for (int i = 1; i < {}; i++) {{", len)?;
+        writeln!(writer, "        if (row < {}_rows[i]) {{", name)?;
+        writeln!(
+            writer,
+            "            return {0}segment(row, {1}_rows[i - 1], {1}_rows[i], {1}_values[i - 1], {1}_values[i],",
+            PREFIX,
+            name
+        )?;
+        writeln!(
+            writer,
+            "                                {}_interpolations[i - 1]);",
+            name
+        )?;
+        writeln!(writer, "        }}")?;
+        writeln!(writer, "    }}")?;
+        writeln!(writer, "    return {};", last_value)?;
+        writeln!(writer, "}}")?;
+    }
+    Ok(())
+}
+
+/// Write tracks as GLSL functions into a `String`, see [`write`](fn@write).
+///
+/// # Errors
+///
+/// See [`write`](fn@write).
+pub fn to_string(tracks: &[Track]) -> io::Result<String> {
+    let mut buf = Vec::new();
+    write(&mut buf, tracks)?;
+    // Identifiers are ASCII, and track names aren't written
+    Ok(String::from_utf8(buf).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::shader::tests::fade_track;
+    use crate::track::tests::sample_project;
+
+    #[test]
+    fn generated_functions() {
+        let glsl = to_string(&sample_project()).unwrap();
+        assert!(glsl.starts_with("// Generated by rust-rocket from 4 tracks."));
+        assert!(glsl.contains("const float sync_camera_x_rows[24] = float[24](0.0, 1.0, 4.0, 9.0,"));
+        assert!(glsl.contains("const int sync_camera_fov_interpolations[24] = int[24](2, 2,"));
+        assert!(glsl.contains("float sync_camera_x(float row) {\n    if (row < 1.0) return 0.0;\n"));
+        assert!(glsl.contains("    if (row >= 74.0) return 37.333332;\n"));
+        assert!(glsl.contains("float sync_single(float row) { return 1e-7; }\n"));
+        assert!(glsl.ends_with("float sync_empty(float row) { return 0.0; }\n"));
+    }
+
+    /// The full output for a small track, which [`shader::tests`] reimplements on the CPU.
+    /// Changing the generated code must change both.
+    #[test]
+    fn exact_output() {
+        assert_eq!(
+            to_string(&[fade_track()]).unwrap(),
+            r#"// Generated by rust-rocket from 1 tracks. Do not edit.
+
+float sync_interpolate(int interpolation, float t) {
+    if (interpolation == 1) return t;
+    if (interpolation == 2) return t * t * (3.0 - 2.0 * t);
+    if (interpolation == 3) return t * t;
+    return 0.0;
+}
+
+float sync_segment(float row, float lower_row, float higher_row, float lower_value, float higher_value,
+                   int interpolation) {
+    float t = (row - lower_row) / (higher_row - lower_row);
+    return lower_value + (higher_value - lower_value) * sync_interpolate(interpolation, t);
+}
+
+const float sync_fade_rows[3] = float[3](0.0, 4.0, 10.0);
+const float sync_fade_values[3] = float[3](1.0, 2.5, -1.0);
+const int sync_fade_interpolations[3] = int[3](1, 2, 0);
+
+float sync_fade(float row) {
+    if (row < 1.0) return 1.0;
+    if (row >= 10.0) return -1.0;
+    for (int i = 1; i < 3; i++) {
+        if (row < sync_fade_rows[i]) {
+            return sync_segment(row, sync_fade_rows[i - 1], sync_fade_rows[i], sync_fade_values[i - 1], sync_fade_values[i],
+                                sync_fade_interpolations[i - 1]);
+        }
+    }
+    return -1.0;
+}
+"#
+        );
+    }
+
+    /// Parse and validate the generated functions in a fragment shader.
+    #[test]
+    fn valid_glsl() {
+        let shader = format!(
+            "#version 450\n{}\nlayout(location = 0) in float row;\nlayout(location = 0) out vec4 color;\n\
+             void main() {{\n    color = vec4(sync_camera_x(row), sync_camera_fov(row), sync_single(row), sync_empty(row));\n}}\n",
+            to_string(&sample_project()).unwrap()
+        );
+        let options = naga::front::glsl::Options::from(naga::ShaderStage::Fragment);
+        let module = naga::front::glsl::Frontend::default()
+            .parse(&options, &shader)
+            .unwrap_or_else(|e| panic!("{}\n{}", e.emit_to_string(&shader), shader));
+        naga::valid::Validator::new(Default::default(), Default::default())
+            .validate(&module)
+            .unwrap();
+    }
+}
@@ -0,0 +1,277 @@
+//! Generating Rust source code from saved tracks.
+//!
+//! The generated source contains, without any dependencies and usable in `no_std` builds:
+//!
+//! - A `Key` and a `Track` type, and a `get_value` function that evaluates tracks exactly like
+//!   [`Track::get_value`](crate::track::Track::get_value).
+//! - A `pub static` `Track` for every track, named after the track (see [`codegen`](super)).
+//! - `TRACKS` and `TRACK_NAMES` arrays in the order of the saved tracks, so a [`TrackId`](crate::track::TrackId)
+//!   from the editing session indexes them.
+//!
+//! # Usage
+//!
+//! Generate the source in your production's `build.rs`, from a [track file](crate::file) saved while editing:
+//!
+//! ```rust,no_run
+//! # use rust_rocket::{codegen, file};
+//! # use std::{env, fs::File, path::Path};
+//! // build.rs
+//! fn main() {
+//!     println!("cargo:rerun-if-changed=tracks.bin");
+//!     let file = File::open("tracks.bin").expect("Failed to open tracks.bin");
+//!     let tracks = file::read(file).expect("Failed to read tracks.bin").into_tracks();
+//!
+//!     let out_dir = env::var("OUT_DIR").unwrap();
+//!     let file = File::create(Path::new(&out_dir).join("sync.rs")).unwrap();
+//!     codegen::rust::write(file, &tracks).expect("Failed to write sync.rs");
+//! }
+//! ```
+//!
+//! And include it in the release build:
+//!
+//! ```rust,ignore
+//! #[allow(dead_code)]
+//! mod sync {
+//!     include!(concat!(env!("OUT_DIR"), "/sync.rs"));
+//! }
+//!
+//! let fov = sync::CAMERA_FOV.get_value(row);
+//! ```
+
+use crate::track::Track;
+use std::io::{self, Write};
+
+/// The evaluator that is copied into the generated source.
+const RUNTIME: &str = include_str!("rust_runtime.rs");
+
+/// The runtime's constants for each [`Interpolation`](crate::interpolation::Interpolation), by value.
+const INTERPOLATIONS: [&str; 4] = ["STEP", "LINEAR", "SMOOTH", "RAMP"];
+
+/// Identifiers of the generated source that tracks can't use.
+const RESERVED: &[&str] = &["STEP", "LINEAR", "SMOOTH", "RAMP", "TRACKS", "TRACK_NAMES"];
+
+/// Write tracks as Rust source.
+///
+/// # Errors
+///
+/// Any error from writing to `writer`.
+pub fn write<W: Write>(mut writer: W, tracks: &[Track]) -> io::Result<()> {
+    writeln!(
+        writer,
+        "// Generated by rust-rocket from {} tracks. Do not edit.",
+        tracks.len()
+    )?;
+    writeln!(writer)?;
+    writer.write_all(RUNTIME.as_bytes())?;
+
+    let identifiers = super::identifiers(tracks, RESERVED, &[""]);
+    for (track, identifier) in tracks.iter().zip(&identifiers) {
+        writeln!(writer)?;
+        writeln!(writer, "/// Track {:?}.", track.get_name())?;
+        if track.keys().is_empty() {
+            writeln!(
+                writer,
+                "pub static {}: Track = Track {{ keys: &[] }};",
+                identifier
+            )?;
+            continue;
+        }
+        writeln!(writer, "pub static {}: Track = Track {{", identifier)?;
+        writeln!(writer, "    keys: &[")?;
+        for key in track.keys() {
+            writeln!(
+                writer,
+                "        Key::new({}, {}, {}),",
+                key.get_row(),
+                float_literal(key.get_value()),
+                INTERPOLATIONS[key.get_interpolation() as usize]
+            )?;
+        }
+        writeln!(writer, "    ],")?;
+        writeln!(writer, "}};")?;
+    }
+
+    writeln!(writer)?;
+    writeln!(
+        writer,
+        "/// The names of the tracks, in the order of `TRACKS`."
+    )?;
+    write!(
+        writer,
+        "pub static TRACK_NAMES: [&str; {}] = [",
+        tracks.len()
+    )?;
+    for track in tracks {
+        write!(writer, "\n    {:?},", track.get_name())?;
+    }
+    writeln!(writer, "\n];")?;
+
+    writeln!(writer)?;
+    writeln!(writer, "/// Every track, in the order of the saved tracks.")?;
+    write!(writer, "pub static TRACKS: [&Track; {}] = [", tracks.len())?;
+    for identifier in &identifiers {
+        write!(writer, "\n    &{},", identifier)?;
+    }
+    writeln!(writer, "\n];")
+}
+
+/// Write tracks as Rust source into a `String`, see [`write`](fn@write).
+pub fn to_string(tracks: &[Track]) -> String {
+    let mut buf = Vec::new();
+    // Writing to a Vec can't fail
+    write(&mut buf, tracks).unwrap();
+    // Track names are UTF-8 and everything else is ASCII
+    String::from_utf8(buf).unwrap()
+}
+
+/// Format a float as a Rust literal that parses back to the same value.
+fn float_literal(value: f32) -> String {
+    if value.is_nan() {
+        String::from("f32::NAN")
+    } else if value == f32::INFINITY {
+        String::from("f32::INFINITY")
+    } else if value == f32::NEG_INFINITY {
+        String::from("f32::NEG_INFINITY")
+    } else {
+        super::float_literal(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpolation::Interpolation;
+    use crate::track::Key as TrackKey;
+    use std::{fs, process::Command};
+
+    #[allow(dead_code)]
+    mod runtime {
+        include!("rust_runtime.rs");
+    }
+
+    fn test_tracks() -> Vec<Track> {
+        let mut a = Track::new("camera:x");
+        a.set_key(TrackKey::new(0, 1.0, Interpolation::Step));
+        a.set_key(TrackKey::new(4, -0.1, Interpolation::Linear));
+        a.set_key(TrackKey::new(10, 3.25, Interpolation::Smooth));
+        a.set_key(TrackKey::new(17, 1e-7, Interpolation::Ramp));
+        a.set_key(TrackKey::new(30, 1e20, Interpolation::Linear));
+        a.set_key(TrackKey::new(31, f32::INFINITY, Interpolation::Step));
+        let mut b = Track::new("\"quoted\"\n");
+        b.set_key(TrackKey::new(3, 2.0, Interpolation::Linear));
+        vec![a, b, Track::new("empty")]
+    }
+
+    #[test]
+    fn runtime_matches_track() {
+        for track in test_tracks() {
+            let keys: Vec<runtime::Key> = track
+                .keys()
+                .iter()
+                .map(|key| {
+                    runtime::Key::new(
+                        key.get_row(),
+                        key.get_value(),
+                        key.get_interpolation() as u8,
+                    )
+                })
+                .collect();
+            for row in -4..80 {
+                let row = row as f32 / 2.;
+                assert_eq!(
+                    runtime::get_value(&keys, row).to_bits(),
+                    track.get_value(row).to_bits(),
+                    "{} at row {}",
+                    track.get_name(),
+                    row
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn interpolations_match() {
+        for interpolation in 0..4 {
+            for t in 0..=16 {
+                let t = t as f32 / 16.;
+                assert_eq!(
+                    runtime::interpolate(interpolation, t),
+                    Interpolation::from(interpolation).interpolate(t)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn generated_source() {
+        let source = to_string(&test_tracks());
+        assert!(source.starts_with("// Generated by rust-rocket from 3 tracks."));
+        assert!(source.contains("pub static CAMERA_X: Track = Track {"));
+        assert!(source.contains("        Key::new(4, -0.1, LINEAR),\n"));
+        assert!(source.contains("        Key::new(17, 1e-7, RAMP),\n"));
+        assert!(source.contains("        Key::new(31, f32::INFINITY, STEP),\n"));
+        assert!(source.contains("pub static EMPTY: Track = Track { keys: &[] };"));
+        assert!(source.contains("    \"\\\"quoted\\\"\\n\",\n"));
+        assert!(source.ends_with("    &CAMERA_X,\n    &QUOTED,\n    &EMPTY,\n];\n"));
+    }
+
+    /// Compile the generated source with rustc as a `no_std` library, and into a program that prints its values,
+    /// which must match the tracks. Skipped if rustc can't be run.
+    #[test]
+    fn compiled_source_matches_track() {
+        let tracks = test_tracks();
+        let source = to_string(&tracks);
+        let main = "
+#[allow(dead_code)]
+mod sync {
+    include!(\"sync.rs\");
+}
+
+fn main() {
+    for track in sync::TRACKS.iter() {
+        for row in -4..80 {
+            println!(\"{}\", track.get_value(row as f32 / 2.).to_bits());
+        }
+    }
+}
+";
+
+        let directory =
+            std::env::temp_dir().join(format!("rust-rocket-codegen-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("sync.rs"), &source).unwrap();
+        fs::write(directory.join("lib.rs"), format!("#![no_std]\n{}", source)).unwrap();
+        fs::write(directory.join("main.rs"), main).unwrap();
+        let rustc = |args: &[&str]| {
+            Command::new("rustc")
+                .args(["--edition=2018", "--out-dir"])
+                .arg(&directory)
+                .args(args)
+                .current_dir(&directory)
+                .status()
+        };
+
+        match rustc(&["--crate-type=lib", "--emit=metadata", "lib.rs"]) {
+            Ok(status) => assert!(status.success(), "Generated source doesn't build as no_std"),
+            Err(_) => {
+                fs::remove_dir_all(&directory).unwrap();
+                eprintln!("rustc not available, skipping");
+                return;
+            }
+        }
+        assert!(rustc(&["main.rs"]).unwrap().success());
+
+        let output = Command::new(directory.join("main")).output().unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+        let values: Vec<u32> = String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(|line| line.parse().unwrap())
+            .collect();
+        let expected: Vec<u32> = tracks
+            .iter()
+            .flat_map(|track| (-4..80).map(move |row| track.get_value(row as f32 / 2.).to_bits()))
+            .collect();
+        assert_eq!(values, expected);
+    }
+}
@@ -0,0 +1,80 @@
+/// Hold the value of the key until the next key.
+pub const STEP: u8 = 0;
+/// Interpolate linearly to the next key.
+pub const LINEAR: u8 = 1;
+/// Interpolate smoothly to the next key, `t * t * (3 - 2 * t)`.
+pub const SMOOTH: u8 = 2;
+/// Interpolate quadratically to the next key, `t * t`.
+pub const RAMP: u8 = 3;
+
+/// A key of a generated track.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Key {
+    /// The row of the key.
+    pub row: u32,
+    /// The value of the key.
+    pub value: f32,
+    /// The interpolation used between this key and the next one.
+    pub interpolation: u8,
+}
+
+impl Key {
+    /// Construct a new `Key`.
+    pub const fn new(row: u32, value: f32, interpolation: u8) -> Key {
+        Key {
+            row,
+            value,
+            interpolation,
+        }
+    }
+}
+
+/// A generated track, its keys sorted by row.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Track {
+    /// The keys of the track.
+    pub keys: &'static [Key],
+}
+
+impl Track {
+    /// Get a value based on a row.
+    pub fn get_value(&self, row: f32) -> f32 {
+        get_value(self.keys, row)
+    }
+}
+
+/// Get a value of a track based on a row, like `rust_rocket::track::Track::get_value`.
+pub fn get_value(keys: &[Key], row: f32) -> f32 {
+    let (first, last) = match (keys.first(), keys.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return 0.0,
+    };
+
+    // Rows before the first key are clamped, so truncating is the same as flooring
+    let lower_row = row as u32;
+    if lower_row <= first.row {
+        return first.value;
+    }
+    if lower_row >= last.row {
+        return last.value;
+    }
+
+    let pos = keys.partition_point(|key| key.row <= lower_row) - 1;
+    let lower = &keys[pos];
+    let higher = &keys[pos + 1];
+
+    let t = (row - (lower.row as f32)) / ((higher.row as f32) - (lower.row as f32));
+    let it = interpolate(lower.interpolation, t);
+
+    lower.value + (higher.value - lower.value) * it
+}
+
+/// Perform an interpolation, like `rust_rocket::interpolation::Interpolation::interpolate`.
+pub fn interpolate(interpolation: u8, t: f32) -> f32 {
+    match interpolation {
+        LINEAR => t,
+        SMOOTH => t * t * (3.0 - 2.0 * t),
+        RAMP => t * t,
+        _ => 0.0,
+    }
+}
@@ -0,0 +1,199 @@
+//! Shared code of the [`glsl`](super::glsl) and [`wgsl`](super::wgsl) generators.
+//!
+//! Both generate the same code for every track: a table of the keys' rows, values and interpolations, and a function
+//! that clamps the row to the first and last keys and otherwise searches the table linearly for the segment
+//! containing the row. Rows are compared as floats, so the clamping and the segment search are written in terms of
+//! the float row, which gives the same results as [`Track::get_value`] flooring the row.
+
+use crate::track::Track;
+use std::io;
+
+/// Rows must be below this, so that every row and the row after it are exact as floats.
+const MAX_ROW: u32 = 1 << 24;
+
+/// Identifiers of the generated code that tracks can't use, before lowercasing.
+const RESERVED: &[&str] = &["INTERPOLATE", "SEGMENT"];
+
+/// Suffixes of the identifiers generated for each track, before lowercasing.
+const SUFFIXES: &[&str] = &["", "_ROWS", "_VALUES", "_INTERPOLATIONS"];
+
+/// The prefix of every identifier in the generated code.
+pub(super) const PREFIX: &str = "sync_";
+
+/// Get the identifier of the function of each track, in the order of `tracks`.
+///
+/// # Errors
+///
+/// [`io::ErrorKind::InvalidData`] if a track has a key that can't be used in shaders.
+pub(super) fn identifiers(tracks: &[Track]) -> io::Result<Vec<String>> {
+    for track in tracks {
+        for key in track.keys() {
+            if key.get_row() >= MAX_ROW {
+                return Err(invalid_data(format!(
+                    "Row {} is too large for shaders in track {}",
+                    key.get_row(),
+                    track.get_name()
+                )));
+            }
+            if !key.get_value().is_finite() {
+                return Err(invalid_data(format!(
+                    "Value {} at row {} can't be used in shaders in track {}",
+                    key.get_value(),
+                    key.get_row(),
+                    track.get_name()
+                )));
+            }
+        }
+    }
+
+    Ok(super::identifiers(tracks, RESERVED, SUFFIXES)
+        .into_iter()
+        .map(|identifier| format!("{}{}", PREFIX, identifier.to_ascii_lowercase()))
+        .collect())
+}
+
+/// Format a row as a float literal.
+pub(super) fn row_literal(row: u32) -> String {
+    super::float_literal(row as f32)
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use crate::interpolation::Interpolation;
+    use crate::track::{tests::sample_project, Key};
+
+    /// A track whose generated code is compared in full by the `exact_output` tests of the generators.
+    pub(in crate::codegen) fn fade_track() -> Track {
+        let mut fade = Track::new("fade");
+        fade.set_key(Key::new(0, 1.0, Interpolation::Linear));
+        fade.set_key(Key::new(4, 2.5, Interpolation::Smooth));
+        fade.set_key(Key::new(10, -1.0, Interpolation::Step));
+        fade
+    }
+
+    /// A CPU implementation of the generated shader code, line by line.
+    ///
+    /// The `exact_output` tests of [`glsl`](crate::codegen::glsl) and [`wgsl`](crate::codegen::wgsl) pin the
+    /// generated code that this mirrors, so a change to it fails a test until this is updated too.
+    fn reference_value(keys: &[Key], row: f32) -> f32 {
+        let rows: Vec<f32> = keys.iter().map(|key| key.get_row() as f32).collect();
+        let values: Vec<f32> = keys.iter().map(Key::get_value).collect();
+        let interpolations: Vec<i32> = keys
+            .iter()
+            .map(|key| key.get_interpolation() as i32)
+            .collect();
+
+        match keys.len() {
+            0 => 0.0,
+            1 => values[0],
+            len => {
+                if row < rows[0] + 1.0 {
+                    return values[0];
+                }
+                if row >= rows[len - 1] {
+                    return values[len - 1];
+                }
+                for i in 1..len {
+                    if row < rows[i] {
+                        return reference_segment(
+                            row,
+                            rows[i - 1],
+                            rows[i],
+                            values[i - 1],
+                            values[i],
+                            interpolations[i - 1],
+                        );
+                    }
+                }
+                values[len - 1]
+            }
+        }
+    }
+
+    /// `sync_segment` of the generated shader code.
+    fn reference_segment(
+        row: f32,
+        lower_row: f32,
+        higher_row: f32,
+        lower_value: f32,
+        higher_value: f32,
+        interpolation: i32,
+    ) -> f32 {
+        let t = (row - lower_row) / (higher_row - lower_row);
+        lower_value + (higher_value - lower_value) * reference_interpolate(interpolation, t)
+    }
+
+    /// `sync_interpolate` of the generated shader code.
+    fn reference_interpolate(interpolation: i32, t: f32) -> f32 {
+        if interpolation == 1 {
+            return t;
+        }
+        if interpolation == 2 {
+            return t * t * (3.0 - 2.0 * t);
+        }
+        if interpolation == 3 {
+            return t * t;
+        }
+        0.0
+    }
+
+    #[test]
+    fn reference_matches_track() {
+        for track in sample_project().into_iter().chain([fade_track()]) {
+            let last_row = track.keys().last().map_or(0, Key::get_row);
+            let rows = (-8..=(last_row as i32 + 2) * 4)
+                .map(|row| row as f32 / 4.)
+                .chain([1e9, f32::INFINITY, f32::NEG_INFINITY]);
+            for row in rows {
+                assert_eq!(
+                    reference_value(track.keys(), row).to_bits(),
+                    track.get_value(row).to_bits(),
+                    "{} at row {}",
+                    track.get_name(),
+                    row
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn reference_interpolations_match() {
+        for interpolation in 0..4u8 {
+            for t in 0..=64 {
+                let t = t as f32 / 64.;
+                assert_eq!(
+                    reference_interpolate(i32::from(interpolation), t).to_bits(),
+                    Interpolation::from(interpolation).interpolate(t).to_bits()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_keys() {
+        let mut track = Track::new("a");
+        track.set_key(Key::new(MAX_ROW, 1.0, Interpolation::Step));
+        let error = identifiers(&[track]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let mut track = Track::new("a");
+        track.set_key(Key::new(0, f32::NAN, Interpolation::Step));
+        let error = identifiers(&[track]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let tracks = [
+            Track::new("camera:x"),
+            Track::new("segment"),
+            Track::new("x rows"),
+        ];
+        assert_eq!(
+            identifiers(&tracks).unwrap(),
+            ["sync_camera_x", "sync_segment_2", "sync_x_rows"]
+        );
+    }
+}
@@ -0,0 +1,253 @@
+//! Generating WGSL functions from saved tracks.
+//!
+//! The generated code contains a `fn sync_<track>(row: f32) -> f32` function for every track, named after the
+//! track (see [`codegen`](super)), e.g. `fn sync_camera_x(row: f32) -> f32`.
+//! Each function evaluates a constant table of the track's keys like [`Track::get_value`], using only
+//! single precision arithmetic.
+//!
+//! GPUs don't always round like the CPU, so values can differ in the last bits between a shader and
+//! [`Track::get_value`], depending on the GPU and the driver. A NaN row gives an unspecified value.
+//!
+//! # Usage
+//!
+//! ```rust,no_run
+//! # use rust_rocket::{codegen, file};
+//! # use std::fs::File;
+//! let file = File::open("tracks.bin").expect("Failed to open tracks.bin");
+//! let tracks = file::read(file).expect("Failed to read tracks.bin").into_tracks();
+//!
+//! let sync = codegen::wgsl::to_string(&tracks).expect("Tracks can't be used in shaders");
+//! # let fragment = "";
+//! // let fragment = include_str!("shader.wgsl");
+//! let shader = format!("{}\n{}", sync, fragment);
+//! ```
+//!
+//! ```wgsl
+//! // shader.wgsl
+//! @group(0) @binding(0) var<uniform> row: f32;
+//!
+//! @fragment
+//! fn main() -> @location(0) vec4<f32> {
+//!     return vec4<f32>(sync_camera_x(row));
+//! }
+//! ```
+
+use super::shader::{self, PREFIX};
+use crate::track::Track;
+use std::io::{self, Write};
+
+/// The functions shared by every track, see [`shader`].
+const HELPERS: &str = "\
+fn sync_interpolate(interpolation: i32, t: f32) -> f32 {
+    if (interpolation == 1) { return t; }
+    if (interpolation == 2) { return t * t * (3.0 - 2.0 * t); }
+    if (interpolation == 3) { return t * t; }
+    return 0.0;
+}
+
+fn sync_segment(row: f32, lower_row: f32, higher_row: f32, lower_value: f32, higher_value: f32,
+                interpolation: i32) -> f32 {
+    let t = (row - lower_row) / (higher_row - lower_row);
+    return lower_value + (higher_value - lower_value) * sync_interpolate(interpolation, t);
+}
+";
+
+/// Write tracks as WGSL functions.
+///
+/// # Errors
+///
+/// Any error from writing to `writer`, or [`io::ErrorKind::InvalidData`] if a key has a row of
+/// 2<sup>24</sup> or more, or a value that isn't finite. Shaders can't represent those exactly.
+pub fn write<W: Write>(mut writer: W, tracks: &[Track]) -> io::Result<()> {
+    let identifiers = shader::identifiers(tracks)?;
+
+    writeln!(
+        writer,
+        "// Generated by rust-rocket from {} tracks. Do not edit.",
+        tracks.len()
+    )?;
+    writeln!(writer)?;
+    writer.write_all(HELPERS.as_bytes())?;
+
+    for (track, name) in tracks.iter().zip(&identifiers) {
+        writeln!(writer)?;
+        let keys = track.keys();
+        let (first, last) = match keys {
+            [] => {
+                writeln!(writer, "fn {}(row: f32) -> f32 {{ return 0.0; }}", name)?;
+                continue;
+            }
+            [key] => {
+                let value = super::float_literal(key.get_value());
+                writeln!(
+                    writer,
+                    "fn {}(row: f32) -> f32 {{ return {}; }}",
+                    name, value
+                )?;
+                continue;
+            }
+            [first, .., last] => (first, last),
+        };
+
+        let len = keys.len();
+        let rows: Vec<String> = keys
+            .iter()
+            .map(|key| shader::row_literal(key.get_row()))
+            .collect();
+        let values: Vec<String> = keys
+            .iter()
+            .map(|key| super::float_literal(key.get_value()))
+            .collect();
+        let interpolations: Vec<String> = keys
+            .iter()
+            .map(|key| (key.get_interpolation() as u8).to_string())
+            .collect();
+        // Private variables, because WGSL implementations don't all allow indexing constants dynamically
+        writeln!(
+            writer,
+            "var<private> {}_rows: array<f32, {}> = array<f32, {1}>({});",
+            name,
+            len,
+            rows.join(", ")
+        )?;
+        writeln!(
+            writer,
+            "var<private> {}_values: array<f32, {}> = array<f32, {1}>({});",
+            name,
+            len,
+            values.join(", ")
+        )?;
+        writeln!(
+            writer,
+            "var<private> {}_interpolations: array<i32, {}> = array<i32, {1}>({});",
+            name,
+            len,
+            interpolations.join(", ")
+        )?;
+
+        let last_value = super::float_literal(last.get_value());
+        writeln!(writer)?;
+        writeln!(writer, "fn {}(row: f32) -> f32 {{", name)?;
+        writeln!(
+            writer,
+            "    if (row < {}) {{ return {}; }}",
+            shader::row_literal(first.get_row() + 1),
+            super::float_literal(first.get_value())
+        )?;
+        writeln!(
+            writer,
+            "    if (row >= {}) {{ return {}; }}",
+            shader::row_literal(last.get_row()),
+            last_value
+        )?;
+        writeln!(writer, "    for (var i = 1; i < {}; i++) {{", len)?;
+        writeln!(writer, "        if (row < {}_rows[i]) {{", name)?;
+        writeln!(
+            writer,
+            "            return {0}segment(row, {1}_rows[i - 1], {1}_rows[i], {1}_values[i - 1], {1}_values[i],",
+            PREFIX,
+            name
+        )?;
+        writeln!(
+            writer,
+            "                                {}_interpolations[i - 1]);",
+            name
+        )?;
+        writeln!(writer, "        }}")?;
+        writeln!(writer, "    }}")?;
+        writeln!(writer, "    return {};", last_value)?;
+        writeln!(writer, "}}")?;
+    }
+    Ok(())
+}
+
+/// Write tracks as WGSL functions into a `String`, see [`write`](fn@write).
+///
+/// # Errors
+///
+/// See [`write`](fn@write).
+pub fn to_string(tracks: &[Track]) -> io::Result<String> {
+    let mut buf = Vec::new();
+    write(&mut buf, tracks)?;
+    // Identifiers are ASCII, and track names aren't written
+    Ok(String::from_utf8(buf).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::shader::tests::fade_track;
+    use crate::track::tests::sample_project;
+
+    #[test]
+    fn generated_functions() {
+        let wgsl = to_string(&sample_project()).unwrap();
+        assert!(wgsl.starts_with("// Generated by rust-rocket from 4 tracks."));
+        assert!(wgsl.contains(
+            "var<private> sync_camera_x_rows: array<f32, 24> = array<f32, 24>(0.0, 1.0, 4.0, 9.0,"
+        ));
+        assert!(wgsl.contains(
+            "var<private> sync_camera_fov_interpolations: array<i32, 24> = array<i32, 24>(2, 2,"
+        ));
+        assert!(wgsl
+            .contains("fn sync_camera_x(row: f32) -> f32 {\n    if (row < 1.0) { return 0.0; }\n"));
+        assert!(wgsl.contains("    if (row >= 74.0) { return 37.333332; }\n"));
+        assert!(wgsl.contains("fn sync_single(row: f32) -> f32 { return 1e-7; }\n"));
+        assert!(wgsl.ends_with("fn sync_empty(row: f32) -> f32 { return 0.0; }\n"));
+    }
+
+    /// The full output for a small track, which [`shader::tests`] reimplements on the CPU.
+    /// Changing the generated code must change both.
+    #[test]
+    fn exact_output() {
+        assert_eq!(
+            to_string(&[fade_track()]).unwrap(),
+            r#"// Generated by rust-rocket from 1 tracks. Do not edit.
+
+fn sync_interpolate(interpolation: i32, t: f32) -> f32 {
+    if (interpolation == 1) { return t; }
+    if (interpolation == 2) { return t * t * (3.0 - 2.0 * t); }
+    if (interpolation == 3) { return t * t; }
+    return 0.0;
+}
+
+fn sync_segment(row: f32, lower_row: f32, higher_row: f32, lower_value: f32, higher_value: f32,
+                interpolation: i32) -> f32 {
+    let t = (row - lower_row) / (higher_row - lower_row);
+    return lower_value + (higher_value - lower_value) * sync_interpolate(interpolation, t);
+}
+
+var<private> sync_fade_rows: array<f32, 3> = array<f32, 3>(0.0, 4.0, 10.0);
+var<private> sync_fade_values: array<f32, 3> = array<f32, 3>(1.0, 2.5, -1.0);
+var<private> sync_fade_interpolations: array<i32, 3> = array<i32, 3>(1, 2, 0);
+
+fn sync_fade(row: f32) -> f32 {
+    if (row < 1.0) { return 1.0; }
+    if (row >= 10.0) { return -1.0; }
+    for (var i = 1; i < 3; i++) {
+        if (row < sync_fade_rows[i]) {
+            return sync_segment(row, sync_fade_rows[i - 1], sync_fade_rows[i], sync_fade_values[i - 1], sync_fade_values[i],
+                                sync_fade_interpolations[i - 1]);
+        }
+    }
+    return -1.0;
+}
+"#
+        );
+    }
+
+    /// Parse and validate the generated functions in a fragment shader.
+    #[test]
+    fn valid_wgsl() {
+        let shader = format!(
+            "{}\n@fragment\nfn main(@location(0) row: f32) -> @location(0) vec4<f32> {{\n    \
+             return vec4<f32>(sync_camera_x(row), sync_camera_fov(row), sync_single(row), sync_empty(row));\n}}\n",
+            to_string(&sample_project()).unwrap()
+        );
+        let module = naga::front::wgsl::parse_str(&shader)
+            .unwrap_or_else(|e| panic!("{}\n{}", e.emit_to_string(&shader), shader));
+        naga::valid::Validator::new(Default::default(), Default::default())
+            .validate(&module)
+            .unwrap();
+    }
+}
@@ -0,0 +1,287 @@
+//! A versioned, self-describing file format for [`Tracks`].
+//!
+//! The layout of track files is documented in [`view`](crate::view#layout), which plays tracks directly from
+//! the bytes of a track file.
+//!
+//! Files written by older versions of this crate, which contain plain [`bincode`](https://crates.io/crates/bincode)
+//! encoded [`Tracks`], can be read when the `bincode` feature is enabled. They are upgraded when written again.
+//!
+//! # Usage
+//!
+//! ```rust,no_run
+//! # use rust_rocket::{file::{self, Metadata}, RocketClient, RocketPlayer};
+//! # use std::fs::File;
+//! # let rocket = RocketClient::new()?;
+//! // Save tracks in your editing build
+//! let file = File::create("tracks.bin").expect("Failed to create tracks.bin");
+//! file::write(file, rocket.save_tracks(), Metadata::new(120., 8))
+//!     .expect("Failed to write tracks.bin");
+//!
+//! // And load them in your release build
+//! let file = File::open("tracks.bin").expect("Failed to open tracks.bin");
+//! let player = RocketPlayer::load(file).expect("Failed to read tracks.bin");
+//! # Ok::<(), rust_rocket::client::Error>(())
+//! ```
+
+use crate::track::Track;
+use crate::view::{self, crc32, PlayerView, HEADER_LEN};
+use crate::Tracks;
+
+use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
+use std::{
+    convert::TryFrom,
+    io::{self, Read, Write},
+};
+use thiserror::Error;
+
+pub use crate::view::{Metadata, FORMAT_VERSION, MAGIC};
+
+/// Version number of files that contain plain bincode encoded [`Tracks`] without a header.
+#[cfg(feature = "bincode")]
+const LEGACY_VERSION: u16 = 0;
+
+/// The `Error` Type for reading track files.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// Reading failed
+    #[error("Failed to read track file")]
+    Io(#[source] io::Error),
+    /// The file is invalid or has an unsupported version.
+    /// [`view::Error::NotATrackFile`] also means that it isn't a file from an older version of this crate.
+    #[error(transparent)]
+    View(#[from] view::Error),
+}
+
+/// The contents of a track file, returned by [`read`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackFile {
+    tracks: Tracks,
+    metadata: Option<Metadata>,
+    version: u16,
+}
+
+impl TrackFile {
+    /// Get the tracks.
+    pub fn get_tracks(&self) -> &Tracks {
+        &self.tracks
+    }
+
+    /// Get the metadata, or `None` if the file was written by an older version of this crate without metadata.
+    pub fn get_metadata(&self) -> Option<Metadata> {
+        self.metadata
+    }
+
+    /// Get the format version of the file. Version 0 is a file from an older version of this crate.
+    pub fn get_version(&self) -> u16 {
+        self.version
+    }
+
+    /// Get the tracks, consuming the file.
+    pub fn into_tracks(self) -> Tracks {
+        self.tracks
+    }
+}
+
+/// Read a track file.
+///
+/// # Errors
+///
+/// [`Error::Io`] if reading fails, or another [`Error`](enum@Error) if the file is corrupt or incompatible.
+pub fn read<R: Read>(mut reader: R) -> Result<TrackFile, Error> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes).map_err(Error::Io)?;
+    from_bytes(&bytes)
+}
+
+/// Read a track file from memory, e.g. from [`include_bytes!`].
+///
+/// # Errors
+///
+/// See [`read`].
+pub fn from_bytes(bytes: &[u8]) -> Result<TrackFile, Error> {
+    if !bytes.starts_with(&MAGIC) {
+        return read_legacy(bytes);
+    }
+
+    // A truncated header is reported by the view
+    let version = match bytes.get(MAGIC.len()..MAGIC.len() + 2) {
+        Some(version) => LittleEndian::read_u16(version),
+        None => FORMAT_VERSION,
+    };
+    match version {
+        FORMAT_VERSION => {
+            let view = PlayerView::new(bytes)?;
+            Ok(TrackFile {
+                tracks: view.to_tracks(),
+                metadata: Some(view.get_metadata()),
+                version: FORMAT_VERSION,
+            })
+        }
+        // Decoders for older versions go here, converting their tracks to the current representation
+        version => Err(view::Error::UnsupportedVersion(version).into()),
+    }
+}
+
+#[cfg(feature = "bincode")]
+fn read_legacy(bytes: &[u8]) -> Result<TrackFile, Error> {
+    match bincode::decode_from_slice::<Tracks, _>(bytes, bincode::config::standard()) {
+        Ok((tracks, len)) if len == bytes.len() => Ok(TrackFile {
+            tracks,
+            metadata: None,
+            version: LEGACY_VERSION,
+        }),
+        _ => Err(view::Error::NotATrackFile.into()),
+    }
+}
+
+#[cfg(not(feature = "bincode"))]
+fn read_legacy(_bytes: &[u8]) -> Result<TrackFile, Error> {
+    Err(view::Error::NotATrackFile.into())
+}
+
+/// Write tracks to a track file.
+///
+/// # Errors
+///
+/// Any error from writing to `writer`.
+///
+/// # Panics
+///
+/// If the tracks take more than [`u32::MAX`] bytes.
+pub fn write<W: Write>(mut writer: W, tracks: &[Track], metadata: Metadata) -> io::Result<()> {
+    writer.write_all(&to_bytes(tracks, metadata))
+}
+
+/// Write tracks to a track file in memory, see [`write`](fn@write).
+pub fn to_bytes(tracks: &[Track], metadata: Metadata) -> Vec<u8> {
+    let mut payload = Vec::new();
+    // Writing to a Vec can't fail
+    payload
+        .write_u32::<LittleEndian>(len_u32(tracks.len()))
+        .unwrap();
+    for track in tracks {
+        let name = track.get_name().as_bytes();
+        payload
+            .write_u32::<LittleEndian>(len_u32(name.len()))
+            .unwrap();
+        payload.extend_from_slice(name);
+        payload
+            .write_u32::<LittleEndian>(len_u32(track.keys().len()))
+            .unwrap();
+        for key in track.keys() {
+            payload.write_u32::<LittleEndian>(key.get_row()).unwrap();
+            payload.write_f32::<LittleEndian>(key.get_value()).unwrap();
+            payload.push(key.get_interpolation() as u8);
+        }
+    }
+
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+    bytes.extend_from_slice(&MAGIC);
+    bytes.write_u16::<LittleEndian>(FORMAT_VERSION).unwrap();
+    bytes.write_u16::<LittleEndian>(0).unwrap();
+    bytes.write_f32::<LittleEndian>(metadata.get_bpm()).unwrap();
+    bytes
+        .write_u32::<LittleEndian>(metadata.get_rows_per_beat())
+        .unwrap();
+    bytes
+        .write_u32::<LittleEndian>(len_u32(payload.len()))
+        .unwrap();
+    bytes.write_u32::<LittleEndian>(crc32(&payload)).unwrap();
+    bytes.extend_from_slice(&payload);
+    bytes
+}
+
+fn len_u32(len: usize) -> u32 {
+    u32::try_from(len).expect("Tracks are too large for a track file")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpolation::Interpolation;
+    use crate::track::Key;
+    use crate::view::KEY_LEN;
+
+    fn test_tracks() -> Tracks {
+        let mut a = Track::new("camera:x");
+        a.set_key(Key::new(0, 1.0, Interpolation::Step));
+        a.set_key(Key::new(16, -2.5, Interpolation::Ramp));
+        vec![a, Track::new("empty ä")]
+    }
+
+    #[test]
+    fn roundtrip() {
+        let metadata = Metadata::new(128., 4);
+        let bytes = to_bytes(&test_tracks(), metadata);
+        assert_eq!(bytes[..8], MAGIC);
+
+        let file = read(&bytes[..]).unwrap();
+        assert_eq!(file.get_version(), FORMAT_VERSION);
+        assert_eq!(file.get_metadata(), Some(metadata));
+        assert_eq!(file.into_tracks(), test_tracks());
+    }
+
+    #[test]
+    fn checksum() {
+        // Check value of the CRC-32 algorithm
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+
+        let mut bytes = to_bytes(&test_tracks(), Metadata::new(120., 8));
+        *bytes.last_mut().unwrap() ^= 1;
+        assert!(matches!(
+            from_bytes(&bytes),
+            Err(Error::View(view::Error::ChecksumMismatch { .. }))
+        ));
+
+        bytes.pop();
+        assert!(matches!(
+            from_bytes(&bytes),
+            Err(Error::View(view::Error::ChecksumMismatch { .. }))
+        ));
+        assert!(matches!(
+            from_bytes(&bytes[..HEADER_LEN - 1]),
+            Err(Error::View(view::Error::Corrupt(_)))
+        ));
+    }
+
+    #[test]
+    fn incompatible() {
+        let mut bytes = to_bytes(&test_tracks(), Metadata::new(120., 8));
+        bytes[8] = 2;
+        assert!(matches!(
+            from_bytes(&bytes),
+            Err(Error::View(view::Error::UnsupportedVersion(2)))
+        ));
+        assert!(matches!(
+            from_bytes(b"hello, world"),
+            Err(Error::View(view::Error::NotATrackFile))
+        ));
+    }
+
+    #[test]
+    fn corrupt_contents() {
+        // A valid checksum over an invalid payload
+        let mut bytes = to_bytes(&test_tracks(), Metadata::new(120., 8));
+        let interpolation = HEADER_LEN + 4 + 4 + 8 + 4 + KEY_LEN - 1;
+        bytes[interpolation] = 9;
+        let checksum = crc32(&bytes[HEADER_LEN..]);
+        bytes[24..28].copy_from_slice(&checksum.to_le_bytes());
+        assert!(matches!(
+            from_bytes(&bytes),
+            Err(Error::View(view::Error::Corrupt("unknown interpolation")))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "bincode")]
+    fn legacy_upgrade() {
+        let legacy = bincode::encode_to_vec(test_tracks(), bincode::config::standard()).unwrap();
+        let file = from_bytes(&legacy).unwrap();
+        assert_eq!(file.get_version(), 0);
+        assert_eq!(file.get_metadata(), None);
+
+        let metadata = Metadata::new(120., 8);
+        let upgraded = to_bytes(file.get_tracks(), metadata);
+        assert_eq!(from_bytes(&upgraded).unwrap().into_tracks(), test_tracks());
+    }
+}
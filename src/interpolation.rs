@@ -4,7 +4,7 @@
 /// This represents the various forms of interpolation that can be performed.
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Interpolation {
     /// `0`
     Step = 0,
@@ -12,23 +12,39 @@ pub enum Interpolation {
     Linear = 1,
     /// `t * t * (3 - 2 * t)`
     Smooth = 2,
-    /// `t.powi(2)`
+    /// `t * t`
     Ramp = 3,
 }
 
+/// Unknown values are converted to [`Interpolation::Step`]. Use [`Interpolation::from_u8`] to detect them.
 impl From<u8> for Interpolation {
     fn from(raw: u8) -> Interpolation {
-        match raw {
-            0 => Interpolation::Step,
-            1 => Interpolation::Linear,
-            2 => Interpolation::Smooth,
-            3 => Interpolation::Ramp,
-            _ => Interpolation::Step,
-        }
+        Interpolation::from_u8(raw).unwrap_or(Interpolation::Step)
     }
 }
 
 impl Interpolation {
+    /// Convert from the raw value used in the Rocket protocol and file formats.
+    ///
+    /// Returns `None` if `raw` isn't a known interpolation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_rocket::interpolation::Interpolation;
+    /// assert_eq!(Interpolation::from_u8(2), Some(Interpolation::Smooth));
+    /// assert_eq!(Interpolation::from_u8(4), None);
+    /// ```
+    pub fn from_u8(raw: u8) -> Option<Interpolation> {
+        match raw {
+            0 => Some(Interpolation::Step),
+            1 => Some(Interpolation::Linear),
+            2 => Some(Interpolation::Smooth),
+            3 => Some(Interpolation::Ramp),
+            _ => None,
+        }
+    }
+
     /// This performs the interpolation.
     ///
     /// # Examples
@@ -47,7 +63,7 @@ impl Interpolation {
             Interpolation::Step => 0.0,
             Interpolation::Linear => t,
             Interpolation::Smooth => t * t * (3.0 - 2.0 * t),
-            Interpolation::Ramp => t.powi(2),
+            Interpolation::Ramp => t * t,
         }
     }
 }
@@ -14,29 +14,103 @@
 //!
 //! The [`player`] module contains a player which you can use when building your production in release mode.
 //!
+//! The [`async_client`] module contains an asynchronous client for use with [tokio](https://crates.io/crates/tokio).
+//! Requires enabling the `tokio` feature.
+//!
+//! The [`threaded`] module runs a client on a background thread and shares track state with other threads.
+//!
+//! The [`protocol`] module implements the Rocket protocol without any IO, for use with other transports.
+//!
+//! The [`server`] module contains a mock tracker for testing your production without a real tracker.
+//!
+//! The [`file`](mod@file) module reads and writes track files for release builds, with a versioned header and a checksum.
+//!
+//! The [`view`] module plays tracks directly from the bytes of a track file, e.g. from [`include_bytes!`],
+//! without decoding or allocating.
+//!
+//! The [`librocket`] module reads and writes the `.track` files of the C librocket.
+//!
+//! The [`xml`] module reads and writes GNU Rocket editor projects. Requires enabling the `xml` feature.
+//!
+//! The [`codegen`] module generates source code from tracks, to compile sync data into a production.
+//!
+//! The [`audit`] module reports tracks that are never read, missing, empty or constant, to find stale and
+//! forgotten tracks before a release.
+//!
+//! The [`source`] module maps structs to groups of tracks, which can be read from a client or a player.
+//!
 //! # Features
 //!
 //! | Feature   | Purpose                                                                           |
 //! | ---       | ---                                                                               |
+//! | `std`     | Enabled by default. Enables everything that needs the standard library, see below |
 //! | `serde`   | Derive [serde](https://crates.io/crates/serde)'s traits on the [`Track`]-type     |
 //! | `bincode` | Derive [bincode](https://crates.io/crates/bincode)'s traits on the [`Track`]-type |
 //! | `simple`  | Enables the [`simple`] API                                                        |
 //! | `player`  | Builds the [`simple`] API in file player mode instead of client mode              |
+//! | `tokio`   | Enables the [`async_client`] API                                                  |
+//! | `derive`  | Enables `#[derive(RocketTracks)]`, see [`source`]                                 |
+//! | `xml`     | Enables the [`xml`] module for GNU Rocket editor projects                         |
 //!
 //! All features are mutually compatible, but if you choose to use `bincode` as your serialization library,
 //! you don't need to use `serde`.
 //!
-//! The `simple` feature enables `bincode`.
+//! The `simple` feature enables `bincode`. The `simple`, `tokio` and `xml` features enable `std`.
+//!
+//! # `no_std`
+//!
+//! Without the default `std` feature, the crate is `no_std` and only needs [`alloc`].
+//! Then it contains the [`track`], [`interpolation`], [`player`], [`view`] and [`audit`] modules for playing tracks,
+//! and [`TrackSource`](source::TrackSource) and `sample` of [`RocketTracks`].
+//! The `serde`, `bincode` and `derive` features also work without `std`.
+//! Building without `std` requires Rust 1.81, because errors then implement `core::error::Error`.
+//!
+//! ```toml
+//! [dependencies]
+//! rust-rocket = { version = "0", default-features = false }
+//! ```
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+// Lets the derive macro refer to ::rust_rocket from within this crate
+#[cfg(all(test, feature = "derive"))]
+extern crate self as rust_rocket;
 
+#[cfg(feature = "tokio")]
+pub mod async_client;
+pub mod audit;
+#[cfg(feature = "std")]
 pub mod client;
+#[cfg(feature = "std")]
+pub mod codegen;
+#[cfg(feature = "std")]
+pub mod file;
 pub mod interpolation;
+#[cfg(feature = "std")]
+pub mod librocket;
 pub mod player;
+#[cfg(feature = "std")]
+pub mod protocol;
+#[cfg(feature = "std")]
+pub mod server;
 pub mod simple;
+pub mod source;
+#[cfg(feature = "std")]
+pub mod threaded;
 pub mod track;
+pub mod view;
+#[cfg(feature = "xml")]
+pub mod xml;
 
+#[cfg(feature = "std")]
 pub use client::RocketClient;
 pub use player::RocketPlayer;
+#[cfg(feature = "derive")]
+pub use rust_rocket_derive::RocketTracks;
+pub use source::RocketTracks;
 pub use track::Track;
 
 /// Produced by [`RocketClient::save_tracks`] and consumed by [`RocketPlayer::new`]
-pub type Tracks = Vec<Track>;
+pub type Tracks = alloc::vec::Vec<Track>;
@@ -0,0 +1,329 @@
+//! Reading and writing the binary `.track` files of the C [librocket](https://github.com/rocket/rocket).
+//!
+//! librocket's `sync_save_tracks` writes one file per track, named `<prefix>_<name>.track`, where the track name
+//! is escaped with [`path_encode`]. Its player mode reads the same files.
+//! Each file contains the number of keys as a 32-bit integer, followed by each key's row (32-bit integer),
+//! value (32-bit float) and interpolation (8-bit integer).
+//! librocket writes the numbers in the byte order of the machine, and this module uses little-endian,
+//! which is the byte order of x86 and ARM machines.
+//!
+//! # Usage
+//!
+//! Load tracks exported by a C/C++ production for playback:
+//!
+//! ```rust,no_run
+//! # use rust_rocket::{librocket, RocketPlayer};
+//! // Reads sync_camera-3Ax.track etc. from the data directory
+//! let tracks = librocket::load_all("data/sync").expect("Failed to load tracks");
+//! let player = RocketPlayer::new(tracks);
+//! ```
+//!
+//! And export tracks for a C/C++ production:
+//!
+//! ```rust,no_run
+//! # use rust_rocket::{librocket, RocketClient};
+//! let mut rocket = RocketClient::new()?;
+//!
+//! // Create tracks, call poll_events, etc...
+//!
+//! librocket::save_tracks("data/sync", rocket.save_tracks()).expect("Failed to save tracks");
+//! # Ok::<(), rust_rocket::client::Error>(())
+//! ```
+
+use crate::interpolation::Interpolation;
+use crate::track::{Key, Track};
+use crate::Tracks;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::{
+    convert::TryFrom,
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+const EXTENSION: &str = ".track";
+const HEX_DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+
+/// Escape a track name for use in a file name, like librocket.
+///
+/// ASCII letters, digits, `.` and `_` are kept. Every other byte is replaced with `-` and two uppercase hex digits.
+///
+/// # Examples
+///
+/// ```rust
+/// # use rust_rocket::librocket::path_encode;
+/// assert_eq!(path_encode("camera:pos x"), "camera-3Apos-20x");
+/// ```
+pub fn path_encode(name: &str) -> String {
+    let mut encoded = String::with_capacity(name.len());
+    for &byte in name.as_bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'.' || byte == b'_' {
+            encoded.push(char::from(byte));
+        } else {
+            encoded.push('-');
+            encoded.push(char::from(HEX_DIGITS[usize::from(byte >> 4)]));
+            encoded.push(char::from(HEX_DIGITS[usize::from(byte & 0xf)]));
+        }
+    }
+    encoded
+}
+
+/// Reverse [`path_encode`].
+///
+/// Returns `None` if `encoded` isn't a valid escaped name.
+///
+/// # Examples
+///
+/// ```rust
+/// # use rust_rocket::librocket::path_decode;
+/// assert_eq!(path_decode("camera-3Apos-20x").as_deref(), Some("camera:pos x"));
+/// assert_eq!(path_decode("camera-3"), None);
+/// ```
+pub fn path_decode(encoded: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut iter = encoded.bytes();
+    while let Some(byte) = iter.next() {
+        match byte {
+            b'-' => {
+                let high = hex_value(iter.next()?)?;
+                let low = hex_value(iter.next()?)?;
+                bytes.push(high << 4 | low);
+            }
+            b'.' | b'_' => bytes.push(byte),
+            _ if byte.is_ascii_alphanumeric() => bytes.push(byte),
+            _ => return None,
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+fn hex_value(digit: u8) -> Option<u8> {
+    char::from(digit).to_digit(16).map(|value| value as u8)
+}
+
+/// Get the path of the file for track `name`, `<prefix>_<name>.track`.
+///
+/// # Examples
+///
+/// ```rust
+/// # use rust_rocket::librocket::track_path;
+/// # use std::path::Path;
+/// assert_eq!(track_path("data/sync", "camera:x"), Path::new("data/sync_camera-3Ax.track"));
+/// ```
+pub fn track_path(prefix: impl AsRef<Path>, name: &str) -> PathBuf {
+    let mut path = OsString::from(prefix.as_ref());
+    path.push("_");
+    path.push(path_encode(name));
+    path.push(EXTENSION);
+    PathBuf::from(path)
+}
+
+/// Read the keys of a track from a `.track` file.
+///
+/// # Errors
+///
+/// Any error from reading, or [`io::ErrorKind::InvalidData`] if a key has an unknown interpolation
+/// or a negative row.
+pub fn read_track<R: Read>(mut reader: R, name: &str) -> io::Result<Track> {
+    let mut track = Track::new(name);
+    let num_keys = reader.read_u32::<LittleEndian>()?;
+    for _ in 0..num_keys {
+        let row = reader.read_i32::<LittleEndian>()?;
+        let value = reader.read_f32::<LittleEndian>()?;
+        let interpolation = reader.read_u8()?;
+
+        let row = u32::try_from(row)
+            .map_err(|_| invalid_data(format!("Negative row {} in track {}", row, name)))?;
+        let interpolation = Interpolation::from_u8(interpolation).ok_or_else(|| {
+            invalid_data(format!(
+                "Unknown interpolation {} in track {}",
+                interpolation, name
+            ))
+        })?;
+        track.set_key(Key::new(row, value, interpolation));
+    }
+    Ok(track)
+}
+
+/// Write the keys of a track in the `.track` format.
+///
+/// # Errors
+///
+/// Any error from writing, or [`io::ErrorKind::InvalidData`] if a row doesn't fit in librocket's signed integer.
+pub fn write_track<W: Write>(mut writer: W, track: &Track) -> io::Result<()> {
+    let keys = track.keys();
+    let num_keys = u32::try_from(keys.len())
+        .map_err(|_| invalid_data(format!("Too many keys in track {}", track.get_name())))?;
+    writer.write_u32::<LittleEndian>(num_keys)?;
+    for key in keys {
+        let row = i32::try_from(key.get_row()).map_err(|_| {
+            invalid_data(format!(
+                "Row {} is too large in track {}",
+                key.get_row(),
+                track.get_name()
+            ))
+        })?;
+        writer.write_i32::<LittleEndian>(row)?;
+        writer.write_f32::<LittleEndian>(key.get_value())?;
+        writer.write_u8(key.get_interpolation() as u8)?;
+    }
+    Ok(())
+}
+
+/// Load the named tracks from `<prefix>_<name>.track` files, in the order of `names`.
+///
+/// # Errors
+///
+/// Any error from [`read_track`], e.g. [`io::ErrorKind::NotFound`] if a track has no file.
+pub fn load_tracks<S: AsRef<str>>(prefix: impl AsRef<Path>, names: &[S]) -> io::Result<Tracks> {
+    let prefix = prefix.as_ref();
+    names
+        .iter()
+        .map(|name| {
+            let name = name.as_ref();
+            let file = File::open(track_path(prefix, name))?;
+            read_track(BufReader::new(file), name)
+        })
+        .collect()
+}
+
+/// Load every track with a `<prefix>_<name>.track` file, sorted by name.
+///
+/// Files whose names aren't valid escaped track names are skipped.
+///
+/// # Errors
+///
+/// Any error from listing the directory of `prefix`, or from [`read_track`].
+pub fn load_all(prefix: impl AsRef<Path>) -> io::Result<Tracks> {
+    let prefix = prefix.as_ref();
+    let directory = match prefix.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let file_prefix = match prefix.file_name().and_then(|name| name.to_str()) {
+        Some(name) => format!("{}_", name),
+        None => return Err(invalid_data(format!("Invalid prefix {:?}", prefix))),
+    };
+
+    let mut names = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let file_name = entry?.file_name();
+        let name = file_name
+            .to_str()
+            .and_then(|file_name| file_name.strip_prefix(&file_prefix))
+            .and_then(|file_name| file_name.strip_suffix(EXTENSION))
+            .and_then(path_decode);
+        if let Some(name) = name {
+            names.push(name);
+        }
+    }
+    names.sort_unstable();
+
+    load_tracks(prefix, &names)
+}
+
+/// Save every track to a `<prefix>_<name>.track` file, overwriting existing files.
+///
+/// # Errors
+///
+/// Any error from creating the files, or from [`write_track`].
+pub fn save_tracks(prefix: impl AsRef<Path>, tracks: &[Track]) -> io::Result<()> {
+    let prefix = prefix.as_ref();
+    for track in tracks {
+        let mut writer = BufWriter::new(File::create(track_path(prefix, track.get_name()))?);
+        write_track(&mut writer, track)?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_track() -> Track {
+        let mut track = Track::new("camera:x");
+        track.set_key(Key::new(0, 1.0, Interpolation::Step));
+        track.set_key(Key::new(16, -2.5, Interpolation::Smooth));
+        track
+    }
+
+    #[test]
+    fn layout() {
+        let mut buf = Vec::new();
+        write_track(&mut buf, &test_track()).unwrap();
+        let mut expected = vec![2, 0, 0, 0];
+        expected.extend([0, 0, 0, 0]);
+        expected.extend(1.0f32.to_le_bytes());
+        expected.push(0);
+        expected.extend([16, 0, 0, 0]);
+        expected.extend((-2.5f32).to_le_bytes());
+        expected.push(2);
+        assert_eq!(buf, expected);
+
+        assert_eq!(read_track(&buf[..], "camera:x").unwrap(), test_track());
+    }
+
+    #[test]
+    fn invalid_files() {
+        let mut buf = Vec::new();
+        write_track(&mut buf, &test_track()).unwrap();
+
+        let truncated = read_track(&buf[..buf.len() - 1], "a").unwrap_err();
+        assert_eq!(truncated.kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut invalid = buf.clone();
+        *invalid.last_mut().unwrap() = 4;
+        let invalid = read_track(&invalid[..], "a").unwrap_err();
+        assert_eq!(invalid.kind(), io::ErrorKind::InvalidData);
+
+        let mut negative = buf;
+        negative[4..8].copy_from_slice(&(-1i32).to_le_bytes());
+        let negative = read_track(&negative[..], "a").unwrap_err();
+        assert_eq!(negative.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn names() {
+        for name in ["camera:x", "a b/c\\d", "fx.glow_amount", "ä-€", ""] {
+            let encoded = path_encode(name);
+            assert!(encoded
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b"._-".contains(&b)));
+            assert_eq!(path_decode(&encoded).as_deref(), Some(name));
+        }
+        assert_eq!(path_encode("ä"), "-C3-A4");
+        assert_eq!(path_decode("a:b"), None);
+        assert_eq!(path_decode("-ZZ"), None);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let directory = std::env::temp_dir().join(format!("rust-rocket-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let prefix = directory.join("sync");
+
+        let mut b = Track::new("b/c");
+        b.set_key(Key::new(3, 4.0, Interpolation::Ramp));
+        let tracks = vec![b, test_track()];
+        save_tracks(&prefix, &tracks).unwrap();
+        fs::write(directory.join("other_camera-3Ax.track"), b"").unwrap();
+        assert!(prefix.with_file_name("sync_b-2Fc.track").is_file());
+
+        let loaded = load_tracks(&prefix, &["camera:x", "b/c"]).unwrap();
+        assert_eq!(loaded, [tracks[1].clone(), tracks[0].clone()]);
+        assert_eq!(load_all(&prefix).unwrap(), tracks);
+        assert_eq!(
+            load_tracks(&prefix, &["missing"]).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
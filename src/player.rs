@@ -1,7 +1,38 @@
 //! Player code for playing previously saved tracks, [`RocketPlayer`].
 
-use crate::{track::Track, Tracks};
-use std::collections::HashMap;
+use crate::audit::{AuditReport, Reads};
+#[cfg(feature = "std")]
+use crate::file;
+use crate::{
+    track::{Track, TrackId},
+    Tracks,
+};
+use alloc::{boxed::Box, vec::Vec};
+// alloc has no HashMap
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeMap as Map;
+#[cfg(feature = "std")]
+use std::{collections::HashMap as Map, io::Read};
+
+/// What [`RocketPlayer::require_track_id`] does when a track doesn't exist.
+///
+/// With [`Default`](Self::Default) and [`Log`](Self::Log), missing tracks are recorded, see
+/// [`RocketPlayer::get_missing_tracks`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissingTrackPolicy {
+    /// Panic. This is the default.
+    Panic,
+    /// Use the default value of the track from [`RocketPlayer::set_default_value`], or 0 if it has none.
+    Default,
+    /// Print a message to stderr once per track, and use 0. Without the `std` feature nothing is printed.
+    Log,
+}
+
+impl Default for MissingTrackPolicy {
+    fn default() -> Self {
+        MissingTrackPolicy::Panic
+    }
+}
 
 /// A player for tracks from
 /// [`RocketClient::save_tracks`](crate::RocketClient::save_tracks).
@@ -13,41 +44,163 @@ use std::collections::HashMap;
 /// # Examples
 ///
 /// ```rust,no_run
-/// # use rust_rocket::{RocketPlayer, Tracks};
+/// # #[cfg(feature = "std")]
+/// # {
+/// # use rust_rocket::RocketPlayer;
 /// # use std::fs::File;
 /// // Run the demo and edit your sync tracks (see top level documentation),
-/// // then call save_tracks and serialize tracks to a file (see save_tracks documentation)
+/// // then call save_tracks and write the tracks to a file (see save_tracks documentation)
 ///
-/// // And deserialize from a file in your release build
-/// let mut file = File::open("tracks.bin").expect("Failed to open tracks.bin");
-/// # #[cfg(feature = "bincode")]
-/// # {
-/// let tracks = bincode::decode_from_std_read(&mut file, bincode::config::standard())
-///     .expect("Failed to decode tracks.bin");
-/// let player = RocketPlayer::new(tracks);
+/// // And load them from the file in your release build
+/// let file = File::open("tracks.bin").expect("Failed to open tracks.bin");
+/// let player = RocketPlayer::load(file).expect("Failed to read tracks.bin");
 /// println!("Value at row 123: {}", player.get_track("test").unwrap().get_value(123.));
 /// # }
-/// # Ok::<(), rust_rocket::client::Error>(())
 /// ```
 pub struct RocketPlayer {
-    tracks: HashMap<Box<str>, Track>,
+    tracks: Tracks,
+    indices: Map<Box<str>, usize>,
+    policy: MissingTrackPolicy,
+    defaults: Map<Box<str>, f32>,
+    /// Names of missing tracks, a [`TrackId`] past the end of `tracks` refers to these
+    missing: Vec<Box<str>>,
+    /// Which tracks have been read, parallel to `tracks`
+    reads: Reads,
 }
 
 impl RocketPlayer {
     /// Constructs a `RocketPlayer` from `Track`s.
+    ///
+    /// The order of `tracks` is kept, so [`TrackId`]s match the client that saved them.
     pub fn new(tracks: Tracks) -> Self {
-        // Convert to a HashMap for perf (not benchmarked)
-        let tracks: HashMap<Box<str>, Track> = tracks
-            .into_iter()
-            .map(|track| (String::from(track.get_name()).into_boxed_str(), track))
+        // Index by name for perf (not benchmarked)
+        let indices = tracks
+            .iter()
+            .enumerate()
+            .map(|(index, track)| (Box::from(track.get_name()), index))
             .collect();
 
-        Self { tracks }
+        Self {
+            reads: Reads::new(tracks.len()),
+            tracks,
+            indices,
+            policy: MissingTrackPolicy::default(),
+            defaults: Map::new(),
+            missing: Vec::new(),
+        }
+    }
+
+    /// Constructs a `RocketPlayer` from a [track file](crate::file). Requires the `std` feature.
+    ///
+    /// # Errors
+    ///
+    /// Any [`file::Error`] from [`file::read`].
+    #[cfg(feature = "std")]
+    pub fn load<R: Read>(reader: R) -> Result<Self, file::Error> {
+        file::read(reader).map(|file| Self::new(file.into_tracks()))
     }
 
     /// Get track by name.
     pub fn get_track(&self, name: &str) -> Option<&Track> {
-        self.tracks.get(name)
+        self.get_track_id(name)
+            .and_then(|id| self.get_track_by_id(id))
+    }
+
+    /// Get a handle to a track by name, for fast lookups with [`get_track_by_id`](Self::get_track_by_id).
+    pub fn get_track_id(&self, name: &str) -> Option<TrackId> {
+        self.indices.get(name).copied().map(TrackId::new)
+    }
+
+    /// Get track by a handle from [`get_track_id`](Self::get_track_id).
+    pub fn get_track_by_id(&self, id: TrackId) -> Option<&Track> {
+        self.reads.mark(id.index());
+        self.tracks.get(id.index())
+    }
+
+    /// Set what [`require_track_id`](Self::require_track_id) does when a track doesn't exist.
+    pub fn set_missing_track_policy(&mut self, policy: MissingTrackPolicy) {
+        self.policy = policy;
+    }
+
+    /// Get the policy set with [`set_missing_track_policy`](Self::set_missing_track_policy).
+    pub fn get_missing_track_policy(&self) -> MissingTrackPolicy {
+        self.policy
+    }
+
+    /// Set the value of a track for when it doesn't exist, used with [`MissingTrackPolicy::Default`].
+    pub fn set_default_value(&mut self, name: &str, value: f32) {
+        self.defaults.insert(Box::from(name), value);
+    }
+
+    /// Get a handle to a track by name, for [`get_value_by_id`](Self::get_value_by_id).
+    ///
+    /// If the track doesn't exist, it's handled according to the [`MissingTrackPolicy`] and recorded as missing,
+    /// and the handle gives the value of the missing track.
+    ///
+    /// # Panics
+    ///
+    /// If the track doesn't exist and the policy is [`MissingTrackPolicy::Panic`].
+    pub fn require_track_id(&mut self, name: &str) -> TrackId {
+        self.require_track_id_with(name, |name| {
+            #[cfg(feature = "std")]
+            eprintln!("Track {} doesn't exist, using 0", name);
+            #[cfg(not(feature = "std"))]
+            let _ = name;
+        })
+    }
+
+    /// [`require_track_id`](Self::require_track_id), where `log` prints the message of [`MissingTrackPolicy::Log`].
+    pub(crate) fn require_track_id_with(&mut self, name: &str, log: impl FnOnce(&str)) -> TrackId {
+        if let Some(id) = self.get_track_id(name) {
+            return id;
+        }
+        if let Some(index) = self.missing.iter().position(|n| &**n == name) {
+            return TrackId::new(self.tracks.len() + index);
+        }
+
+        match self.policy {
+            MissingTrackPolicy::Panic => panic!("Track {} doesn't exist", name),
+            MissingTrackPolicy::Default => (),
+            MissingTrackPolicy::Log => log(name),
+        }
+        self.missing.push(Box::from(name));
+        TrackId::new(self.tracks.len() + self.missing.len() - 1)
+    }
+
+    /// Get the value of a track at `row`, by a handle from [`get_track_id`](Self::get_track_id) or
+    /// [`require_track_id`](Self::require_track_id).
+    ///
+    /// Missing tracks have the value given by the [`MissingTrackPolicy`]. Returns 0 if the handle is not from
+    /// this `RocketPlayer`.
+    pub fn get_value_by_id(&self, id: TrackId, row: f32) -> f32 {
+        if let Some(track) = self.get_track_by_id(id) {
+            return track.get_value(row);
+        }
+        match self.missing.get(id.index().wrapping_sub(self.tracks.len())) {
+            Some(name) if self.policy == MissingTrackPolicy::Default => {
+                self.defaults.get(name).copied().unwrap_or(0.)
+            }
+            _ => 0.,
+        }
+    }
+
+    /// Get the names of the tracks that [`require_track_id`](Self::require_track_id) didn't find,
+    /// in the order they were first requested.
+    ///
+    /// Report these at exit, so a release build that is missing tracks still plays, and tells what was missing.
+    pub fn get_missing_tracks(&self) -> impl Iterator<Item = &str> + '_ {
+        self.missing.iter().map(|name| &**name)
+    }
+
+    /// Report tracks that were never read, missing, empty or constant, see [`audit`](crate::audit).
+    ///
+    /// Missing tracks are the ones from [`get_missing_tracks`](Self::get_missing_tracks).
+    pub fn audit(&self) -> AuditReport {
+        AuditReport::from_reads(
+            &self.tracks,
+            |index| self.reads.get(index),
+            self.get_missing_tracks(),
+        )
     }
 }
 
@@ -95,4 +248,67 @@ mod tests {
             .get_track("hello this track should not exist")
             .is_none());
     }
+
+    #[test]
+    fn track_ids_match_saved_order() {
+        let tracks = get_test_tracks();
+        let player = RocketPlayer::new(tracks);
+        let id = player.get_track_id("test2").unwrap();
+        assert_eq!(id.index(), 1);
+        assert_eq!(player.get_track_by_id(id).unwrap().get_name(), "test2");
+        assert!(player.get_track_id("test3").is_none());
+    }
+
+    #[test]
+    fn missing_tracks() {
+        let mut player = RocketPlayer::new(get_test_tracks());
+        player.set_missing_track_policy(MissingTrackPolicy::Default);
+        player.set_default_value("fov", 45.);
+
+        let fov = player.require_track_id("fov");
+        let test2 = player.require_track_id("test2");
+        let speed = player.require_track_id("speed");
+        assert_eq!(player.require_track_id("fov"), fov);
+        assert_ne!(fov, speed);
+        assert_eq!(player.get_value_by_id(fov, 3.), 45.);
+        assert_eq!(player.get_value_by_id(speed, 3.), 0.);
+        assert_eq!(player.get_value_by_id(test2, 0.), 2.);
+        assert!(player.get_track_by_id(fov).is_none());
+        assert!(player.get_missing_tracks().eq(["fov", "speed"]));
+
+        player.set_missing_track_policy(MissingTrackPolicy::Log);
+        assert_eq!(player.get_value_by_id(fov, 3.), 0.);
+    }
+
+    #[test]
+    fn audit() {
+        let mut player = RocketPlayer::new(get_test_tracks());
+        player.set_missing_track_policy(MissingTrackPolicy::Default);
+        let test1 = player.get_track_id("test1").unwrap();
+        let fov = player.require_track_id("fov");
+        assert_eq!(player.audit().get_unused_tracks(), ["test1", "test2"]);
+
+        player.get_value_by_id(test1, 0.);
+        player.get_value_by_id(fov, 0.);
+        let report = player.audit();
+        assert_eq!(report.get_unused_tracks(), ["test2"]);
+        assert_eq!(report.get_missing_tracks(), ["fov"]);
+        assert!(report.get_empty_tracks().is_empty());
+        assert!(report.get_constant_tracks().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Track fov doesn't exist")]
+    fn missing_track_panics() {
+        RocketPlayer::new(get_test_tracks()).require_track_id("fov");
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn load_track_file() {
+        let bytes = file::to_bytes(&get_test_tracks(), file::Metadata::new(120., 8));
+        let player = RocketPlayer::load(&bytes[..]).unwrap();
+        assert_eq!(player.get_track("test2").unwrap().get_value(0.), 2.0);
+        assert!(RocketPlayer::load(&bytes[..20]).is_err());
+    }
 }
@@ -0,0 +1,678 @@
+//! Transport-agnostic implementation of the Rocket protocol.
+//!
+//! This module doesn't perform any IO. The [`Handshake`] checks the tracker's greeting,
+//! the [`Decoder`] turns bytes received from a tracker into [`Command`]s,
+//! and the `encode_*` functions write outgoing messages into a buffer.
+//! [`RocketClient`](crate::RocketClient) uses this module on top of a socket,
+//! but you can use it to run the protocol over any transport or to test your own tooling.
+//!
+//! # Examples
+//!
+//! ```
+//! # use rust_rocket::protocol::{Command, Decoder};
+//! let mut decoder = Decoder::new();
+//!
+//! // SET_ROW 1234, split over two reads
+//! decoder.feed(&[3, 0, 0]);
+//! assert_eq!(decoder.decode(), Ok(None));
+//! decoder.feed(&[0x04, 0xd2]);
+//! assert_eq!(decoder.decode(), Ok(Some(Command::SetRow(1234))));
+//! ```
+
+use crate::interpolation::Interpolation;
+use crate::track::Key;
+
+use byteorder::{BigEndian, ByteOrder};
+use std::convert::TryFrom;
+use thiserror::Error;
+
+/// Sent by the client to the tracker when a connection is established.
+pub const CLIENT_GREETING: &[u8] = b"hello, synctracker!";
+/// Sent by the tracker as a response to [`CLIENT_GREETING`].
+pub const SERVER_GREETING: &[u8] = b"hello, demo!";
+
+pub(crate) const SET_KEY: u8 = 0;
+pub(crate) const DELETE_KEY: u8 = 1;
+pub(crate) const GET_TRACK: u8 = 2;
+pub(crate) const SET_ROW: u8 = 3;
+pub(crate) const PAUSE: u8 = 4;
+pub(crate) const SAVE_TRACKS: u8 = 5;
+
+const SET_KEY_LEN: usize = 4 + 4 + 4 + 1;
+const DELETE_KEY_LEN: usize = 4 + 4;
+const GET_TRACK_LEN: usize = 4; // Does not account for name length
+const SET_ROW_LEN: usize = 4;
+const PAUSE_LEN: usize = 1;
+
+/// The longest command the tracker can send, including the command byte.
+pub const MAX_COMMAND_LEN: usize = 1 + SET_KEY_LEN;
+
+/// The longest track name, in bytes, that [`ClientDecoder`] accepts in a GET_TRACK message.
+pub const MAX_TRACK_NAME_LEN: usize = 4096;
+
+/// Errors from [`Decoder::decode`] and [`ClientDecoder::decode`].
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The tracker sent a command byte that is not part of the protocol.
+    ///
+    /// The length of an unknown command can't be known, so the decoder discards all buffered bytes.
+    /// Commands in bytes fed later may be misinterpreted, so the connection should be re-established.
+    #[error("Unknown command: {0:?}")]
+    UnknownCommand(u8),
+    /// The tracker sent a SET_KEY command with an interpolation that is not part of the protocol.
+    ///
+    /// The whole command is consumed, so decoding can continue normally.
+    #[error("Invalid interpolation: {0:?}")]
+    InvalidInterpolation(u8),
+    /// A client sent a GET_TRACK message with a name longer than [`MAX_TRACK_NAME_LEN`].
+    /// Only returned by [`ClientDecoder::decode`].
+    ///
+    /// The decoder discards all buffered bytes, like with [`UnknownCommand`](Self::UnknownCommand).
+    #[error("Track name of {0} bytes is too long")]
+    TrackNameTooLong(u32),
+}
+
+/// The tracker answered the client's greeting with something other than [`SERVER_GREETING`].
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+#[error("Unexpected greeting {0:?}")]
+pub struct GreetingMismatch(pub [u8; SERVER_GREETING.len()]);
+
+/// The client side of the greeting exchange.
+///
+/// Send [`CLIENT_GREETING`], then feed bytes from the tracker until [`check`](Self::check) returns `Ok(true)`.
+///
+/// # Examples
+///
+/// ```
+/// # use rust_rocket::protocol::{Handshake, SERVER_GREETING};
+/// let mut handshake = Handshake::new();
+/// // Send CLIENT_GREETING to the tracker, then receive its answer
+/// assert_eq!(handshake.feed(&SERVER_GREETING[..5]), 5);
+/// assert_eq!(handshake.check(), Ok(false));
+/// assert_eq!(handshake.remaining(), SERVER_GREETING.len() - 5);
+///
+/// // Bytes after the greeting are commands for the Decoder
+/// let received = [&SERVER_GREETING[5..], &[4, 1]].concat();
+/// let len = handshake.feed(&received);
+/// assert_eq!(handshake.check(), Ok(true));
+/// assert_eq!(received[len..], [4, 1]);
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct Handshake {
+    received: [u8; SERVER_GREETING.len()],
+    len: usize,
+}
+
+impl Handshake {
+    /// Construct a new `Handshake`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the amount of greeting bytes that haven't been fed yet.
+    ///
+    /// Reading exactly this many bytes avoids reading commands that follow the greeting.
+    pub fn remaining(&self) -> usize {
+        self.received.len() - self.len
+    }
+
+    /// Feed bytes received from the tracker.
+    ///
+    /// Returns how many of the bytes belong to the greeting. The rest are commands.
+    pub fn feed(&mut self, bytes: &[u8]) -> usize {
+        let len = bytes.len().min(self.remaining());
+        self.received[self.len..][..len].copy_from_slice(&bytes[..len]);
+        self.len += len;
+        len
+    }
+
+    /// Returns `Ok(true)` if the whole greeting has been received, or `Ok(false)` if more bytes need to be fed.
+    ///
+    /// # Errors
+    ///
+    /// [`GreetingMismatch`] if the greeting isn't [`SERVER_GREETING`].
+    pub fn check(&self) -> Result<bool, GreetingMismatch> {
+        if self.remaining() > 0 {
+            Ok(false)
+        } else if self.received == SERVER_GREETING {
+            Ok(true)
+        } else {
+            Err(GreetingMismatch(self.received))
+        }
+    }
+}
+
+/// A command sent by the tracker to the client.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    /// Insert or update a key on the track with index `track`.
+    ///
+    /// Track indices are assigned in the order the client sent [`GET_TRACK`](encode_get_track) messages.
+    SetKey {
+        /// Index of the track
+        track: u32,
+        /// The key to set
+        key: Key,
+    },
+    /// Delete the key at `row` from the track with index `track`.
+    DeleteKey {
+        /// Index of the track
+        track: u32,
+        /// Row of the key to delete
+        row: u32,
+    },
+    /// The tracker changes row.
+    SetRow(u32),
+    /// The tracker pauses or unpauses.
+    Pause(bool),
+    /// The tracker asks the client to save its track data.
+    SaveTracks,
+}
+
+impl Command {
+    /// Append the wire representation of the command to `buf`.
+    ///
+    /// This is the tracker side of the protocol. It's useful for implementing a tracker or for testing a client.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_rocket::protocol::{Command, Decoder};
+    /// let mut buf = Vec::new();
+    /// Command::Pause(true).encode(&mut buf);
+    ///
+    /// let mut decoder = Decoder::new();
+    /// decoder.feed(&buf);
+    /// assert_eq!(decoder.decode(), Ok(Some(Command::Pause(true))));
+    /// ```
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        match *self {
+            Command::SetKey { track, key } => {
+                let mut cmd = [SET_KEY; 1 + SET_KEY_LEN];
+                BigEndian::write_u32(&mut cmd[1..5], track);
+                BigEndian::write_u32(&mut cmd[5..9], key.get_row());
+                BigEndian::write_f32(&mut cmd[9..13], key.get_value());
+                cmd[13] = key.get_interpolation() as u8;
+                buf.extend_from_slice(&cmd);
+            }
+            Command::DeleteKey { track, row } => {
+                let mut cmd = [DELETE_KEY; 1 + DELETE_KEY_LEN];
+                BigEndian::write_u32(&mut cmd[1..5], track);
+                BigEndian::write_u32(&mut cmd[5..9], row);
+                buf.extend_from_slice(&cmd);
+            }
+            Command::SetRow(row) => encode_set_row(buf, row),
+            Command::Pause(flag) => buf.extend_from_slice(&[PAUSE, u8::from(flag)]),
+            Command::SaveTracks => buf.push(SAVE_TRACKS),
+        }
+    }
+}
+
+/// Append a GET_TRACK message to `buf`.
+///
+/// The tracker responds by sending the keys of the track.
+///
+/// # Panics
+///
+/// Will panic if `name`'s length exceeds [`u32::MAX`].
+pub fn encode_get_track(buf: &mut Vec<u8>, name: &str) {
+    let mut cmd = [GET_TRACK; 1 + GET_TRACK_LEN];
+    let name_len = u32::try_from(name.len()).expect("Track name too long");
+    BigEndian::write_u32(&mut cmd[1..], name_len);
+    buf.extend_from_slice(&cmd);
+    buf.extend_from_slice(name.as_bytes());
+}
+
+/// Append a SET_ROW message to `buf`.
+///
+/// This is used by both sides of the protocol.
+pub fn encode_set_row(buf: &mut Vec<u8>, row: u32) {
+    let mut cmd = [SET_ROW; 1 + SET_ROW_LEN];
+    BigEndian::write_u32(&mut cmd[1..], row);
+    buf.extend_from_slice(&cmd);
+}
+
+/// A message sent by the client to the tracker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientCommand {
+    /// The client requests the keys of a track. See [`encode_get_track`].
+    GetTrack(String),
+    /// The client changes row. See [`encode_set_row`].
+    SetRow(u32),
+}
+
+impl ClientCommand {
+    /// Append the wire representation of the message to `buf`.
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            ClientCommand::GetTrack(name) => encode_get_track(buf, name),
+            ClientCommand::SetRow(row) => encode_set_row(buf, *row),
+        }
+    }
+}
+
+/// Bytes that have been fed to a decoder, but not decoded yet.
+#[derive(Debug, Default, Clone)]
+struct InputBuffer {
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl InputBuffer {
+    fn feed(&mut self, bytes: &[u8]) {
+        if self.pos > 0 {
+            self.buf.drain(..self.pos);
+            self.pos = 0;
+        }
+        self.buf.extend_from_slice(bytes);
+    }
+
+    fn clear(&mut self) {
+        self.buf.clear();
+        self.pos = 0;
+    }
+
+    fn remaining(&self) -> &[u8] {
+        &self.buf[self.pos..]
+    }
+
+    fn consume(&mut self, len: usize) {
+        self.pos += len;
+    }
+}
+
+/// Decodes [`Command`]s from bytes received from the tracker.
+///
+/// Bytes can be fed in arbitrarily sized chunks. Incomplete commands are buffered until the rest of the command
+/// has been fed.
+#[derive(Debug, Default, Clone)]
+pub struct Decoder {
+    input: InputBuffer,
+}
+
+impl Decoder {
+    /// Construct a new `Decoder`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append received bytes to the decoder's buffer.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.input.feed(bytes);
+    }
+
+    /// Discard all buffered bytes, e.g. after reconnecting.
+    pub fn clear(&mut self) {
+        self.input.clear();
+    }
+
+    /// Returns the amount of bytes that have been fed, but not decoded yet.
+    pub fn buffered(&self) -> usize {
+        self.input.remaining().len()
+    }
+
+    /// Decode the next command.
+    ///
+    /// Returns `Ok(None)` if more bytes need to be fed before a complete command is available.
+    /// Call this in a loop until it returns `Ok(None)` to decode every buffered command.
+    ///
+    /// # Errors
+    ///
+    /// [`DecodeError::UnknownCommand`] if the next command byte is not part of the protocol,
+    /// or [`DecodeError::InvalidInterpolation`] if a SET_KEY command has an invalid interpolation.
+    /// See the variants for how the decoder recovers.
+    pub fn decode(&mut self) -> Result<Option<Command>, DecodeError> {
+        let input = self.input.remaining();
+        let cmd = match input.first() {
+            Some(&cmd) => cmd,
+            None => return Ok(None),
+        };
+        let len = match cmd {
+            SET_KEY => SET_KEY_LEN,
+            DELETE_KEY => DELETE_KEY_LEN,
+            SET_ROW => SET_ROW_LEN,
+            PAUSE => PAUSE_LEN,
+            SAVE_TRACKS => 0,
+            _ => {
+                self.input.clear();
+                return Err(DecodeError::UnknownCommand(cmd));
+            }
+        };
+        if input.len() < 1 + len {
+            return Ok(None);
+        }
+
+        let data = &input[1..][..len];
+        let command = match cmd {
+            SET_KEY => {
+                let track = BigEndian::read_u32(&data[0..4]);
+                let row = BigEndian::read_u32(&data[4..8]);
+                let value = BigEndian::read_f32(&data[8..12]);
+                let interpolation = match Interpolation::from_u8(data[12]) {
+                    Some(interpolation) => interpolation,
+                    None => {
+                        let raw = data[12];
+                        self.input.consume(1 + len);
+                        return Err(DecodeError::InvalidInterpolation(raw));
+                    }
+                };
+                Command::SetKey {
+                    track,
+                    key: Key::new(row, value, interpolation),
+                }
+            }
+            DELETE_KEY => Command::DeleteKey {
+                track: BigEndian::read_u32(&data[0..4]),
+                row: BigEndian::read_u32(&data[4..8]),
+            },
+            SET_ROW => Command::SetRow(BigEndian::read_u32(data)),
+            PAUSE => Command::Pause(data[0] == 1),
+            SAVE_TRACKS => Command::SaveTracks,
+            _ => unreachable!(),
+        };
+        self.input.consume(1 + len);
+
+        Ok(Some(command))
+    }
+}
+
+/// Decodes [`ClientCommand`]s from bytes received from a client.
+///
+/// This is the tracker side counterpart of [`Decoder`].
+/// The client's greeting must be stripped before feeding bytes to the decoder.
+#[derive(Debug, Default, Clone)]
+pub struct ClientDecoder {
+    input: InputBuffer,
+}
+
+impl ClientDecoder {
+    /// Construct a new `ClientDecoder`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append received bytes to the decoder's buffer.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.input.feed(bytes);
+    }
+
+    /// Decode the next message.
+    ///
+    /// Returns `Ok(None)` if more bytes need to be fed before a complete message is available.
+    /// Track names that are not valid UTF-8 are converted lossily.
+    ///
+    /// # Errors
+    ///
+    /// [`DecodeError::UnknownCommand`] if the next command byte is not part of the protocol,
+    /// or [`DecodeError::TrackNameTooLong`] if a GET_TRACK message has a name longer than [`MAX_TRACK_NAME_LEN`].
+    /// All buffered bytes are discarded.
+    pub fn decode(&mut self) -> Result<Option<ClientCommand>, DecodeError> {
+        let input = self.input.remaining();
+        let (command, len) = match input.first() {
+            None => return Ok(None),
+            Some(&GET_TRACK) => {
+                if input.len() < 1 + GET_TRACK_LEN {
+                    return Ok(None);
+                }
+                let name_len = BigEndian::read_u32(&input[1..][..GET_TRACK_LEN]);
+                // Don't buffer an arbitrary amount of input for a name
+                if name_len as usize > MAX_TRACK_NAME_LEN {
+                    self.input.clear();
+                    return Err(DecodeError::TrackNameTooLong(name_len));
+                }
+                let name_len = name_len as usize;
+                let name = match input[1 + GET_TRACK_LEN..].get(..name_len) {
+                    Some(name) => name,
+                    None => return Ok(None),
+                };
+                let name = String::from_utf8_lossy(name).into_owned();
+                (ClientCommand::GetTrack(name), 1 + GET_TRACK_LEN + name_len)
+            }
+            Some(&SET_ROW) => {
+                if input.len() < 1 + SET_ROW_LEN {
+                    return Ok(None);
+                }
+                let row = BigEndian::read_u32(&input[1..][..SET_ROW_LEN]);
+                (ClientCommand::SetRow(row), 1 + SET_ROW_LEN)
+            }
+            Some(&cmd) => {
+                self.input.clear();
+                return Err(DecodeError::UnknownCommand(cmd));
+            }
+        };
+        self.input.consume(len);
+
+        Ok(Some(command))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_commands() -> Vec<Command> {
+        vec![
+            Command::SetKey {
+                track: 0,
+                key: Key::new(16, -1.5, Interpolation::Smooth),
+            },
+            Command::SetRow(1234),
+            Command::DeleteKey { track: 7, row: 16 },
+            Command::Pause(true),
+            Command::SaveTracks,
+            Command::Pause(false),
+            Command::SetKey {
+                track: u32::MAX,
+                key: Key::new(u32::MAX, f32::MAX, Interpolation::Ramp),
+            },
+        ]
+    }
+
+    fn encode_all(commands: &[Command]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        for command in commands {
+            command.encode(&mut buf);
+        }
+        buf
+    }
+
+    fn decode_all(decoder: &mut Decoder) -> Vec<Command> {
+        let mut commands = Vec::new();
+        while let Some(command) = decoder.decode().unwrap() {
+            commands.push(command);
+        }
+        commands
+    }
+
+    #[test]
+    fn roundtrip() {
+        let commands = test_commands();
+        let mut decoder = Decoder::new();
+        decoder.feed(&encode_all(&commands));
+        assert_eq!(decode_all(&mut decoder), commands);
+        assert_eq!(decoder.buffered(), 0);
+    }
+
+    #[test]
+    fn byte_by_byte() {
+        let commands = test_commands();
+        let mut decoder = Decoder::new();
+        let mut decoded = Vec::new();
+        for byte in encode_all(&commands) {
+            decoder.feed(&[byte]);
+            decoded.extend(decode_all(&mut decoder));
+        }
+        assert_eq!(decoded, commands);
+    }
+
+    #[test]
+    fn unknown_command() {
+        let mut decoder = Decoder::new();
+        decoder.feed(&[SAVE_TRACKS, 42, SAVE_TRACKS]);
+        assert_eq!(decoder.decode(), Ok(Some(Command::SaveTracks)));
+        assert_eq!(decoder.decode(), Err(DecodeError::UnknownCommand(42)));
+        assert_eq!(decoder.decode(), Ok(None));
+        assert_eq!(decoder.buffered(), 0);
+
+        decoder.feed(&[SAVE_TRACKS]);
+        assert_eq!(decoder.decode(), Ok(Some(Command::SaveTracks)));
+    }
+
+    #[test]
+    fn invalid_interpolation() {
+        let mut buf = Vec::new();
+        Command::SetKey {
+            track: 0,
+            key: Key::new(0, 0.0, Interpolation::Step),
+        }
+        .encode(&mut buf);
+        buf[13] = 4;
+        Command::SaveTracks.encode(&mut buf);
+
+        let mut decoder = Decoder::new();
+        decoder.feed(&buf);
+        assert_eq!(decoder.decode(), Err(DecodeError::InvalidInterpolation(4)));
+        assert_eq!(decoder.decode(), Ok(Some(Command::SaveTracks)));
+    }
+
+    /// A small deterministic PRNG (xorshift32) for fuzz-style tests.
+    struct Rng(u32);
+
+    impl Rng {
+        fn next(&mut self) -> u32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            self.0
+        }
+
+        fn below(&mut self, n: u32) -> u32 {
+            self.next() % n
+        }
+    }
+
+    #[test]
+    fn fuzz_random_bytes() {
+        let mut rng = Rng(0x1234_5678);
+        for _ in 0..1000 {
+            let mut decoder = Decoder::new();
+            let len = rng.below(256);
+            let bytes: Vec<u8> = (0..len).map(|_| rng.next() as u8).collect();
+            let mut rest = &bytes[..];
+            while !rest.is_empty() {
+                let (chunk, tail) = rest.split_at((rng.below(32) as usize).min(rest.len()));
+                rest = tail;
+                decoder.feed(chunk);
+                loop {
+                    match decoder.decode() {
+                        Ok(Some(_)) | Err(DecodeError::InvalidInterpolation(_)) => {}
+                        Ok(None) => break,
+                        Err(DecodeError::UnknownCommand(cmd)) => {
+                            assert!(cmd == GET_TRACK || cmd > SAVE_TRACKS);
+                            assert_eq!(decoder.buffered(), 0);
+                        }
+                        Err(e @ DecodeError::TrackNameTooLong(_)) => panic!("{}", e),
+                    }
+                }
+                assert!(decoder.buffered() < MAX_COMMAND_LEN);
+            }
+        }
+    }
+
+    #[test]
+    fn fuzz_mutated_commands() {
+        let mut rng = Rng(0xdead_beef);
+        let valid = encode_all(&test_commands());
+        for _ in 0..1000 {
+            let mut bytes = valid.clone();
+            for _ in 0..=rng.below(4) {
+                let pos = rng.below(bytes.len() as u32) as usize;
+                bytes[pos] = rng.next() as u8;
+            }
+            let mut decoder = Decoder::new();
+            decoder.feed(&bytes);
+            while let Ok(Some(_)) | Err(DecodeError::InvalidInterpolation(_)) = decoder.decode() {}
+            assert!(decoder.buffered() < MAX_COMMAND_LEN);
+        }
+    }
+
+    #[test]
+    fn fuzz_client_decoder() {
+        let mut rng = Rng(0x0bad_cafe);
+        for _ in 0..1000 {
+            let mut decoder = ClientDecoder::new();
+            let bytes: Vec<u8> = (0..rng.below(64)).map(|_| rng.next() as u8).collect();
+            decoder.feed(&bytes);
+            while let Ok(Some(_)) = decoder.decode() {}
+        }
+    }
+
+    #[test]
+    fn client_roundtrip() {
+        let commands = [
+            ClientCommand::GetTrack("camera:x".into()),
+            ClientCommand::SetRow(5),
+            ClientCommand::GetTrack(String::new()),
+        ];
+        let mut buf = Vec::new();
+        for command in &commands {
+            command.encode(&mut buf);
+        }
+
+        let mut decoder = ClientDecoder::new();
+        let mut decoded = Vec::new();
+        for byte in buf {
+            decoder.feed(&[byte]);
+            while let Some(command) = decoder.decode().unwrap() {
+                decoded.push(command);
+            }
+        }
+        assert_eq!(decoded, commands);
+    }
+
+    #[test]
+    fn handshake() {
+        let mut handshake = Handshake::new();
+        for &byte in SERVER_GREETING {
+            assert_eq!(handshake.check(), Ok(false));
+            assert_eq!(handshake.feed(&[byte]), 1);
+        }
+        assert_eq!(handshake.check(), Ok(true));
+        assert_eq!(handshake.feed(&[SET_ROW]), 0);
+
+        let mut handshake = Handshake::new();
+        let greeting = b"hello, demo?";
+        handshake.feed(greeting);
+        assert_eq!(handshake.check(), Err(GreetingMismatch(*greeting)));
+    }
+
+    #[test]
+    fn client_name_too_long() {
+        let mut decoder = ClientDecoder::new();
+        decoder.feed(&[GET_TRACK, 0xff, 0xff, 0xff, 0xff, b'a']);
+        assert_eq!(
+            decoder.decode(),
+            Err(DecodeError::TrackNameTooLong(u32::MAX))
+        );
+        assert_eq!(decoder.decode(), Ok(None));
+
+        let mut buf = Vec::new();
+        encode_get_track(&mut buf, &"a".repeat(MAX_TRACK_NAME_LEN));
+        decoder.feed(&buf);
+        assert!(matches!(
+            decoder.decode(),
+            Ok(Some(ClientCommand::GetTrack(_)))
+        ));
+    }
+
+    #[test]
+    fn client_messages() {
+        let mut buf = Vec::new();
+        encode_get_track(&mut buf, "a:b");
+        encode_set_row(&mut buf, 0x01020304);
+        assert_eq!(
+            buf,
+            [GET_TRACK, 0, 0, 0, 3, b'a', b':', b'b', SET_ROW, 1, 2, 3, 4]
+        );
+    }
+}
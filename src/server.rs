@@ -0,0 +1,476 @@
+//! A stand-in for a Rocket tracker, [`MockTracker`].
+//!
+//! The mock tracker implements the tracker side of the protocol, so you can test your production's
+//! sync handling without running a real tracker.
+//! It answers the client's track requests with scripted keys, and lets you send row changes, pauses,
+//! save requests and key edits to the client.
+//!
+//! # Usage
+//!
+//! The tracker can run over an in-memory [`DuplexStream`], which keeps tests deterministic and single-threaded:
+//!
+//! ```rust
+//! # use rust_rocket::{client::Event, interpolation::Interpolation, server::MockTracker};
+//! # use rust_rocket::track::{Key, Track};
+//! # use rust_rocket::RocketClient;
+//! let mut camera = Track::new("camera:x");
+//! camera.set_key(Key::new(0, 1.0, Interpolation::Linear));
+//! camera.set_key(Key::new(8, 2.0, Interpolation::Step));
+//!
+//! let (mut tracker, stream) = MockTracker::duplex();
+//! tracker.insert_track(camera);
+//! let mut rocket = RocketClient::from_stream(stream)?;
+//!
+//! rocket.get_track_mut("camera:x")?;
+//! tracker.poll()?; // Answers GET_TRACK
+//! tracker.set_row(4)?;
+//!
+//! assert!(matches!(rocket.poll_events()?, Some(Event::SetRow(4))));
+//! assert_eq!(rocket.get_track("camera:x").unwrap().get_value(4.), 1.5);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! Real TCP connections work too, but the client's handshake blocks until the tracker responds,
+//! so the tracker has to be accepted on another thread:
+//!
+//! ```rust
+//! # use rust_rocket::{server::MockTracker, RocketClient};
+//! use std::net::TcpListener;
+//!
+//! let listener = TcpListener::bind("127.0.0.1:0")?;
+//! let addr = listener.local_addr()?;
+//! let tracker = std::thread::spawn(move || MockTracker::accept(&listener));
+//!
+//! let mut rocket = RocketClient::connect(addr)?;
+//! let mut tracker = tracker.join().unwrap()?;
+//!
+//! rocket.set_row(16)?;
+//! // Wait for the message to arrive
+//! while tracker.row().is_none() {
+//!     tracker.poll()?;
+//! }
+//! assert_eq!(tracker.row(), Some(16));
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use crate::protocol::{ClientCommand, ClientDecoder, Command, CLIENT_GREETING, SERVER_GREETING};
+use crate::track::{Key, Track};
+
+use std::{
+    collections::VecDeque,
+    convert::TryFrom,
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+};
+
+/// A mock Rocket tracker.
+///
+/// See [module-level documentation](crate::server#Usage).
+#[derive(Debug)]
+pub struct MockTracker<S = TcpStream> {
+    stream: S,
+    greeting: Vec<u8>,
+    decoder: ClientDecoder,
+    tracks: Vec<Track>,
+    requested: Vec<String>,
+    row: Option<u32>,
+}
+
+impl MockTracker {
+    /// Accept a client from `listener` and perform the handshake.
+    ///
+    /// This blocks until a client connects and sends its greeting.
+    /// The accepted stream is switched to non-blocking mode.
+    ///
+    /// # Errors
+    ///
+    /// Any IO error, or [`io::ErrorKind::InvalidData`] if the client's greeting isn't correct.
+    pub fn accept(listener: &TcpListener) -> io::Result<Self> {
+        let (stream, _) = listener.accept()?;
+        let mut tracker = Self::new(stream)?;
+
+        let mut greeting = [0; CLIENT_GREETING.len()];
+        tracker.stream.read_exact(&mut greeting)?;
+        tracker.receive_greeting(&greeting)?;
+
+        tracker.stream.set_nonblocking(true)?;
+        Ok(tracker)
+    }
+}
+
+impl MockTracker<DuplexStream> {
+    /// Construct a tracker connected to an in-memory stream.
+    ///
+    /// Returns the tracker and the client's end of the stream,
+    /// which can be passed to [`RocketClient::from_stream`](crate::RocketClient::from_stream).
+    pub fn duplex() -> (Self, DuplexStream) {
+        let (tracker_end, client_end) = duplex();
+        let tracker = Self::new(tracker_end).unwrap_or_else(|_| unreachable!());
+        (tracker, client_end)
+    }
+}
+
+impl<S: Read + Write> MockTracker<S> {
+    /// Construct a tracker on top of a stream that is connected to a client.
+    ///
+    /// The tracker's greeting is sent immediately, and the client's greeting is checked by [`poll`](Self::poll).
+    /// `poll` treats [`io::ErrorKind::WouldBlock`] as "no data available", so non-blocking streams are preferred.
+    ///
+    /// # Errors
+    ///
+    /// Any IO error from sending the greeting.
+    pub fn new(mut stream: S) -> io::Result<Self> {
+        stream.write_all(SERVER_GREETING)?;
+        Ok(Self {
+            stream,
+            greeting: Vec::new(),
+            decoder: ClientDecoder::new(),
+            tracks: Vec::new(),
+            requested: Vec::new(),
+            row: None,
+        })
+    }
+
+    /// Get a reference to the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Get a mutable reference to the underlying stream.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    /// Add a track with scripted keys, replacing a previous track with the same name.
+    ///
+    /// The keys are sent to the client when it requests the track.
+    /// If the client has already requested the track, the keys are not sent again.
+    /// Use [`set_key`](Self::set_key) for live edits.
+    pub fn insert_track(&mut self, track: Track) {
+        match self
+            .tracks
+            .iter_mut()
+            .find(|t| t.get_name() == track.get_name())
+        {
+            Some(existing) => *existing = track,
+            None => self.tracks.push(track),
+        }
+    }
+
+    /// Get a scripted track by name, including edits made with [`set_key`](Self::set_key).
+    pub fn get_track(&self, name: &str) -> Option<&Track> {
+        self.tracks.iter().find(|t| t.get_name() == name)
+    }
+
+    /// Names of the tracks the client has requested, in request order.
+    ///
+    /// The position of a name is the track index used in the protocol.
+    pub fn requested_tracks(&self) -> &[String] {
+        &self.requested
+    }
+
+    /// The last row sent by the client, if any.
+    pub fn row(&self) -> Option<u32> {
+        self.row
+    }
+
+    /// Process everything the client has sent so far.
+    ///
+    /// Track requests are answered with the keys of the matching scripted track.
+    ///
+    /// # Errors
+    ///
+    /// Any IO error, [`io::ErrorKind::UnexpectedEof`] if the client disconnected,
+    /// or [`io::ErrorKind::InvalidData`] if the client sent an invalid greeting or an unknown command.
+    pub fn poll(&mut self) -> io::Result<()> {
+        let mut buf = [0; 1024];
+        loop {
+            let bytes_read = match self.stream.read(&mut buf) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(bytes_read) => bytes_read,
+                Err(e) => match e.kind() {
+                    io::ErrorKind::WouldBlock => return Ok(()),
+                    io::ErrorKind::Interrupted => continue,
+                    _ => return Err(e),
+                },
+            };
+            let data = self.receive_greeting(&buf[..bytes_read])?;
+            self.decoder.feed(data);
+
+            while let Some(command) = self
+                .decoder
+                .decode()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+            {
+                match command {
+                    ClientCommand::GetTrack(name) => self.send_track(name)?,
+                    ClientCommand::SetRow(row) => self.row = Some(row),
+                }
+            }
+        }
+    }
+
+    /// Tell the client to change row.
+    pub fn set_row(&mut self, row: u32) -> io::Result<()> {
+        self.send(Command::SetRow(row))
+    }
+
+    /// Tell the client to pause or unpause.
+    pub fn pause(&mut self, flag: bool) -> io::Result<()> {
+        self.send(Command::Pause(flag))
+    }
+
+    /// Ask the client to save its tracks.
+    pub fn save_tracks(&mut self) -> io::Result<()> {
+        self.send(Command::SaveTracks)
+    }
+
+    /// Insert or update a key on a track, creating the track if needed.
+    ///
+    /// The key is sent to the client if it has requested the track.
+    pub fn set_key(&mut self, track: &str, key: Key) -> io::Result<()> {
+        match self.tracks.iter_mut().find(|t| t.get_name() == track) {
+            Some(existing) => existing.set_key(key),
+            None => {
+                let mut new = Track::new(track);
+                new.set_key(key);
+                self.tracks.push(new);
+            }
+        }
+        match self.track_index(track) {
+            Some(index) => self.send(Command::SetKey { track: index, key }),
+            None => Ok(()),
+        }
+    }
+
+    /// Delete a key from a track.
+    ///
+    /// The deletion is sent to the client if it has requested the track.
+    pub fn delete_key(&mut self, track: &str, row: u32) -> io::Result<()> {
+        if let Some(existing) = self.tracks.iter_mut().find(|t| t.get_name() == track) {
+            existing.delete_key(row);
+        }
+        match self.track_index(track) {
+            Some(index) => self.send(Command::DeleteKey { track: index, row }),
+            None => Ok(()),
+        }
+    }
+
+    /// Send any command to the client.
+    ///
+    /// This does not update the scripted tracks. It can be used to send invalid data, e.g. unknown track indices.
+    pub fn send(&mut self, command: Command) -> io::Result<()> {
+        let mut buf = Vec::new();
+        command.encode(&mut buf);
+        self.stream.write_all(&buf)
+    }
+
+    fn track_index(&self, name: &str) -> Option<u32> {
+        self.requested
+            .iter()
+            .position(|requested| requested == name)
+            .map(|index| u32::try_from(index).expect("Too many tracks"))
+    }
+
+    fn send_track(&mut self, name: String) -> io::Result<()> {
+        let index = u32::try_from(self.requested.len()).expect("Too many tracks");
+        let mut buf = Vec::new();
+        if let Some(track) = self.tracks.iter().find(|t| t.get_name() == name) {
+            for &key in track.keys() {
+                Command::SetKey { track: index, key }.encode(&mut buf);
+            }
+        }
+        self.requested.push(name);
+        self.stream.write_all(&buf)
+    }
+
+    /// Strip the client's greeting from the start of the received data.
+    fn receive_greeting<'a>(&mut self, data: &'a [u8]) -> io::Result<&'a [u8]> {
+        let missing = CLIENT_GREETING.len() - self.greeting.len();
+        let (greeting, rest) = data.split_at(missing.min(data.len()));
+        self.greeting.extend_from_slice(greeting);
+        if !CLIENT_GREETING.starts_with(&self.greeting) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Client greeting {:?} wasn't correct", self.greeting),
+            ));
+        }
+        Ok(rest)
+    }
+}
+
+/// One end of an in-memory, bidirectional byte stream. Create a pair with [`duplex`].
+///
+/// Reads return [`io::ErrorKind::WouldBlock`] when no data is available, like a non-blocking socket.
+/// When the other end has been dropped, reads return end-of-file after the remaining data,
+/// and writes fail with [`io::ErrorKind::BrokenPipe`].
+#[derive(Debug)]
+pub struct DuplexStream {
+    input: Arc<Mutex<VecDeque<u8>>>,
+    output: Arc<Mutex<VecDeque<u8>>>,
+}
+
+/// Construct a connected pair of [`DuplexStream`]s.
+pub fn duplex() -> (DuplexStream, DuplexStream) {
+    let a = Arc::new(Mutex::new(VecDeque::new()));
+    let b = Arc::new(Mutex::new(VecDeque::new()));
+    (
+        DuplexStream {
+            input: Arc::clone(&a),
+            output: Arc::clone(&b),
+        },
+        DuplexStream {
+            input: b,
+            output: a,
+        },
+    )
+}
+
+impl DuplexStream {
+    fn is_peer_dropped(&self) -> bool {
+        Arc::strong_count(&self.output) == 1
+    }
+}
+
+impl Read for DuplexStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut input = self.input.lock().unwrap_or_else(|e| e.into_inner());
+        if input.is_empty() && !buf.is_empty() {
+            return if self.is_peer_dropped() {
+                Ok(0)
+            } else {
+                Err(io::ErrorKind::WouldBlock.into())
+            };
+        }
+        input.read(buf)
+    }
+}
+
+impl Write for DuplexStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.is_peer_dropped() {
+            return Err(io::ErrorKind::BrokenPipe.into());
+        }
+        let mut output = self.output.lock().unwrap_or_else(|e| e.into_inner());
+        output.extend(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::client::{Error, Event};
+    use crate::interpolation::Interpolation;
+    use crate::RocketClient;
+    use std::time::{Duration, Instant};
+
+    /// Wait for another thread or a socket, failing the test if it takes longer than 5 seconds.
+    pub(crate) fn wait_until(mut condition: impl FnMut() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !condition() {
+            assert!(Instant::now() < deadline, "Timed out waiting");
+            std::thread::yield_now();
+        }
+    }
+
+    fn test_track() -> Track {
+        let mut track = Track::new("test");
+        track.set_key(Key::new(0, 1.0, Interpolation::Step));
+        track.set_key(Key::new(5, 0.0, Interpolation::Linear));
+        track.set_key(Key::new(10, 1.0, Interpolation::Step));
+        track
+    }
+
+    #[test]
+    fn scripted_tracks() {
+        let (mut tracker, stream) = MockTracker::duplex();
+        tracker.insert_track(test_track());
+        let mut rocket = RocketClient::from_stream(stream).unwrap();
+
+        rocket.get_track_mut("unknown").unwrap();
+        rocket.get_track_mut("test").unwrap();
+        rocket.set_row(3).unwrap();
+        tracker.poll().unwrap();
+        assert_eq!(tracker.requested_tracks(), ["unknown", "test"]);
+        assert_eq!(tracker.row(), Some(3));
+
+        assert!(rocket.poll_events().unwrap().is_none());
+        assert_eq!(rocket.get_track("test"), Some(&test_track()));
+        assert!(rocket.get_track("unknown").unwrap().keys().is_empty());
+    }
+
+    #[test]
+    fn live_edits() {
+        let (mut tracker, stream) = MockTracker::duplex();
+        let mut rocket = RocketClient::from_stream(stream).unwrap();
+        rocket.get_track_mut("test").unwrap();
+        tracker.poll().unwrap();
+
+        for key in test_track().keys() {
+            tracker.set_key("test", *key).unwrap();
+        }
+        tracker
+            .set_key("other", Key::new(1, 1.0, Interpolation::Step))
+            .unwrap();
+        tracker.delete_key("test", 10).unwrap();
+        tracker.pause(true).unwrap();
+        tracker.save_tracks().unwrap();
+
+        assert!(matches!(rocket.poll_events(), Ok(Some(Event::Pause(true)))));
+        assert!(matches!(rocket.poll_events(), Ok(Some(Event::SaveTracks))));
+        assert!(rocket.get_track("other").is_none());
+
+        let track = rocket.get_track("test").unwrap();
+        assert_eq!(track, tracker.get_track("test").unwrap());
+        assert_eq!(track.keys().len(), 2);
+    }
+
+    #[test]
+    fn bad_greeting() {
+        let (tracker_end, mut client_end) = duplex();
+        let mut tracker = MockTracker::new(tracker_end).unwrap();
+        client_end.write_all(b"hello, synctrackers").unwrap();
+        let error = tracker.poll().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn disconnect() {
+        let (tracker, stream) = MockTracker::duplex();
+        let mut rocket = RocketClient::from_stream(stream).unwrap();
+        drop(tracker);
+        assert!(matches!(rocket.poll_events(), Err(Error::IOError(_))));
+    }
+
+    #[test]
+    fn tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let tracker = std::thread::spawn(move || MockTracker::accept(&listener));
+
+        let mut rocket = RocketClient::connect(addr).unwrap();
+        let mut tracker = tracker.join().unwrap().unwrap();
+        tracker.insert_track(test_track());
+
+        rocket.get_track_mut("test").unwrap();
+        wait_until(|| {
+            tracker.poll().unwrap();
+            !tracker.requested_tracks().is_empty()
+        });
+        tracker.set_row(7).unwrap();
+
+        let mut event = None;
+        wait_until(|| {
+            event = rocket.poll_events().unwrap();
+            event.is_some()
+        });
+        assert!(matches!(event, Some(Event::SetRow(7))));
+        assert_eq!(rocket.get_track("test"), Some(&test_track()));
+    }
+}
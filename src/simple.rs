@@ -31,7 +31,7 @@
 //! ```
 //!
 //! A main loop may look like this:
-//! ```rust,no_run
+//! ```rust
 //! # use std::time::Duration;
 //! # use rust_rocket::simple::{Rocket, Event};
 //! struct MusicPlayer; // Your music player, not included in this crate
@@ -43,12 +43,15 @@
 //! #     fn pause(&self, _state: bool) {}
 //! # }
 //!
+//! # #[cfg(not(feature = "player"))]
 //! fn main() {
 //!     let mut music = MusicPlayer::new(/* ... */);
-//!     let mut rocket = Rocket::new("tracks.bin", music.get_bpm()).unwrap();
+//!     // let mut rocket = Rocket::new("tracks.bin", music.get_bpm()).unwrap();
+//! #     let (mut rocket, _tracker) = rust_rocket::simple::mock_rocket(music.get_bpm());
 //!
 //!     // Create window, render resources etc...
 //!
+//! #     let mut frames = 0;
 //!     loop {
 //!         // Handle events from the rocket tracker
 //!         while let Some(event) = rocket.poll_events().ok().flatten() {
@@ -65,8 +68,14 @@
 //!
 //!         // Read values with Rocket's get_value function while rendering the frame
 //!         let _ = rocket.get_value("track0");
+//! #         frames += 1;
+//! #         if frames == 3 {
+//! #             break;
+//! #         }
 //!     }
 //! }
+//! # #[cfg(feature = "player")]
+//! # fn main() {}
 //! ```
 //!
 //! For a more thorough example, see `examples/simple.rs`.
@@ -74,11 +83,13 @@
 //! # Caveats
 //!
 //! - Can't choose how to handle [`saving the tracks`](crate::RocketClient::save_tracks), this uses [`std::fs::File`]
-//!   and [`bincode`].
+//!   and the [track file format](crate::file).
 //! - Sub-optimal performance, the implementation does not support caching tracks
 //!   (only [`get_value`](Rocket::get_value), no [`get_track`](crate::RocketClient::get_track)).
 //!   It's unlikely that this causes noticeable slowdown unless you have an abnormally large amount of tracks.
-//! - **Caution**: reconnection will wipe track state. Make sure to save in the editor before closing and reopening it.
+//!   If it does, look up tracks by [`TrackId`] with [`get_value_by_id`](Rocket::get_value_by_id).
+//! - Reconnection keeps the track state and refreshes it from the tracker, see
+//!   [`RocketClient::reconnect`](crate::RocketClient::reconnect).
 //!
 //! # Benefits
 //!
@@ -86,8 +97,11 @@
 //! - Avoid writing `#[cfg(...)]`-attributes in your code.
 //! - Sensible error handling that you may want to write anyway if you're not size-restricted.
 
-use bincode::error::{DecodeError, EncodeError};
-use std::{path::Path, time::Duration};
+use crate::audit::AuditReport;
+use crate::file;
+use crate::player::MissingTrackPolicy;
+use crate::track::TrackId;
+use std::{io, path::Path, time::Duration};
 
 const SECS_PER_MINUTE: f32 = 60.;
 const ROWS_PER_BEAT: f32 = 8.;
@@ -147,6 +161,8 @@ pub enum Event {
 /// See [module documentation](crate::simple#Usage).
 pub struct Rocket<P: AsRef<Path>> {
     path: P,
+    #[cfg(not(feature = "player"))]
+    bpm: f32,
     bps: f32,
     row: f32,
     #[cfg(not(feature = "player"))]
@@ -157,6 +173,8 @@ pub struct Rocket<P: AsRef<Path>> {
     connection_attempted: std::time::Instant,
     #[cfg(not(feature = "player"))]
     rocket: Option<crate::RocketClient>, // TODO: Make the client work on borrowed tracks so this Option isn't needed
+    #[cfg(not(feature = "player"))]
+    names: Vec<String>, // Track names by TrackId, the client's tracks are created in the same order
     #[cfg(feature = "player")]
     rocket: crate::RocketPlayer,
 }
@@ -170,23 +188,28 @@ impl<P: AsRef<Path>> Rocket<P> {
     ///
     /// # With `player` feature
     ///
-    /// Loads tracks from file specified by `path` using [`bincode`].
+    /// Loads tracks from the [track file](crate::file) specified by `path`.
+    /// Files saved by older versions of this crate are also accepted.
     ///
     /// # Errors
     ///
     /// Any errors that occur are first printed to stderr, then returned to the caller.
     ///
-    /// An error is returned If the file specified by `path` cannot be read or its contents cannot be decoded.
+    /// An error is returned if the file specified by `path` cannot be read, is corrupt, or was saved by a newer
+    /// version of this crate.
     ///
     /// The return value can be handled by calling [`unwrap`](Result::unwrap) if you want to panic,
     /// or [`ok`](Result::ok) if you want to ignore the error and continue without using rocket.
-    pub fn new(path: P, bpm: f32) -> Result<Self, DecodeError> {
+    pub fn new(path: P, bpm: f32) -> Result<Self, file::Error> {
         #[cfg(not(feature = "player"))]
-        let rocket = Self::connect().ok();
+        let rocket = {
+            print_msg(PREFIX, "Connecting...");
+            crate::RocketClient::new().ok()
+        };
 
         #[cfg(feature = "player")]
         let rocket = {
-            let mut file = match std::fs::File::open(&path) {
+            let file = match std::fs::File::open(&path) {
                 Ok(file) => file,
                 Err(e) => {
                     print_msg(
@@ -194,15 +217,14 @@ impl<P: AsRef<Path>> Rocket<P> {
                         &format!("Failed to open {}", path.as_ref().display()),
                     );
                     print_errors(PREFIX, &e);
-                    return Err(DecodeError::Io {
-                        inner: e,
-                        additional: 0,
-                    });
+                    return Err(file::Error::Io(e));
                 }
             };
-            let tracks = match bincode::decode_from_std_read(&mut file, bincode::config::standard())
-            {
-                Ok(tracks) => tracks,
+            let tracks = match file::read(io::BufReader::new(file)) {
+                Ok(file) => {
+                    check_bpm(&file, bpm);
+                    file.into_tracks()
+                }
                 Err(e) => {
                     print_msg(
                         PREFIX,
@@ -217,6 +239,8 @@ impl<P: AsRef<Path>> Rocket<P> {
 
         Ok(Self {
             path,
+            #[cfg(not(feature = "player"))]
+            bpm,
             bps: bpm / SECS_PER_MINUTE,
             row: 0.,
             #[cfg(not(feature = "player"))]
@@ -226,43 +250,208 @@ impl<P: AsRef<Path>> Rocket<P> {
             #[cfg(not(feature = "player"))]
             connection_attempted: std::time::Instant::now(),
             rocket,
+            #[cfg(not(feature = "player"))]
+            names: Vec::new(),
         })
     }
 
+    /// Initializes rocket with a client that is already connected to a tracker, e.g. with
+    /// [`ConnectOptions`](crate::client::ConnectOptions) for another address than the default.
+    /// Reconnecting uses the client's address and options.
+    ///
+    /// Tracks that already exist in the client keep their [`TrackId`]s.
+    ///
+    /// This function is only available when the `player` feature is not enabled.
+    #[cfg(not(feature = "player"))]
+    pub fn from_client(path: P, client: crate::RocketClient, bpm: f32) -> Self {
+        let names = client
+            .save_tracks()
+            .iter()
+            .map(|track| String::from(track.get_name()))
+            .collect();
+        Self {
+            path,
+            bpm,
+            bps: bpm / SECS_PER_MINUTE,
+            row: 0.,
+            tracker_row: 0,
+            connected: true,
+            connection_attempted: std::time::Instant::now(),
+            rocket: Some(client),
+            names,
+        }
+    }
+
     /// Get value based on previous call to [`set_time`](Self::set_time), by track name.
     ///
     /// # Panics
     ///
     /// With `player` feature: if the file specified in call to [`new`](Self::new) doesn't contain track with `name`,
+    /// and the [missing track policy](Self::set_missing_track_policy) is [`MissingTrackPolicy::Panic`],
     /// the function handles the error by printing to stderr and panicking.
     pub fn get_value(&mut self, track: &str) -> f32 {
+        let id = self.get_track_id(track);
+        self.get_value_by_id(id)
+    }
+
+    /// Get a handle to a track by name, for fast lookups with [`get_value_by_id`](Self::get_value_by_id).
+    ///
+    /// Call this during initialization and keep the handle, instead of calling [`get_value`](Self::get_value) with
+    /// the name every frame. The handle stays valid across reconnects.
+    ///
+    /// # Panics
+    ///
+    /// With `player` feature: if the file specified in call to [`new`](Self::new) doesn't contain track with `name`,
+    /// and the [missing track policy](Self::set_missing_track_policy) is [`MissingTrackPolicy::Panic`],
+    /// the function handles the error by printing to stderr and panicking.
+    pub fn get_track_id(&mut self, name: &str) -> TrackId {
         #[cfg(not(feature = "player"))]
-        let track = match self
-            .rocket
-            .as_mut()
-            .and_then(|rocket| rocket.get_track_mut(track).ok())
         {
-            Some(track) => track,
-            None => {
-                self.connected = false;
-                return 0.;
+            if let Some(index) = self.names.iter().position(|n| n == name) {
+                return TrackId::new(index);
             }
-        };
+            self.names.push(String::from(name));
+            if let Some(rocket) = &mut self.rocket {
+                if rocket.get_track_id(name).is_err() {
+                    self.connected = false;
+                }
+            }
+            TrackId::new(self.names.len() - 1)
+        }
 
         #[cfg(feature = "player")]
-        let track = self.rocket.get_track(track).unwrap_or_else(|| {
-            print_msg(
-                PREFIX,
-                &format!(
-                    "Track {} doesn't exist in {}",
-                    track,
-                    self.path.as_ref().display()
-                ),
-            );
-            panic!("{}: Can't recover", PREFIX);
-        });
+        {
+            let policy = self.rocket.get_missing_track_policy();
+            if policy == MissingTrackPolicy::Panic && self.rocket.get_track_id(name).is_none() {
+                print_msg(
+                    PREFIX,
+                    &format!(
+                        "Track {} doesn't exist in {}",
+                        name,
+                        self.path.as_ref().display()
+                    ),
+                );
+                panic!("{}: Can't recover", PREFIX);
+            }
+            let path = self.path.as_ref();
+            self.rocket.require_track_id_with(name, |name| {
+                print_msg(
+                    PREFIX,
+                    &format!(
+                        "Track {} doesn't exist in {}, using 0",
+                        name,
+                        path.display()
+                    ),
+                );
+            })
+        }
+    }
+
+    /// Get value based on previous call to [`set_time`](Self::set_time), by track handle.
+    ///
+    /// Returns 0 if the handle is not from this `Rocket`.
+    pub fn get_value_by_id(&self, id: TrackId) -> f32 {
+        #[cfg(not(feature = "player"))]
+        {
+            self.rocket
+                .as_ref()
+                .and_then(|rocket| rocket.get_track_by_id(id))
+                .map_or(0., |track| track.get_value(self.row))
+        }
+
+        #[cfg(feature = "player")]
+        self.rocket.get_value_by_id(id, self.row)
+    }
+
+    /// Set what happens when a track doesn't exist in the file, see [`MissingTrackPolicy`].
+    /// The default is to panic.
+    ///
+    /// Has no effect without the `player` feature, because the tracker creates missing tracks.
+    pub fn set_missing_track_policy(&mut self, policy: MissingTrackPolicy) {
+        #[cfg(not(feature = "player"))]
+        let _ = policy;
+
+        #[cfg(feature = "player")]
+        self.rocket.set_missing_track_policy(policy);
+    }
+
+    /// Set the value of a track for when it doesn't exist in the file, used with [`MissingTrackPolicy::Default`].
+    ///
+    /// Has no effect without the `player` feature.
+    pub fn set_default_value(&mut self, name: &str, value: f32) {
+        #[cfg(not(feature = "player"))]
+        let _ = (name, value);
 
-        track.get_value(self.row)
+        #[cfg(feature = "player")]
+        self.rocket.set_default_value(name, value);
+    }
+
+    /// Get the names of the tracks that didn't exist in the file, in the order they were first requested.
+    ///
+    /// Always empty without the `player` feature.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # #[cfg(not(feature = "player"))]
+    /// # {
+    /// # use rust_rocket::{player::MissingTrackPolicy, simple::Rocket};
+    /// // let mut rocket = Rocket::new("tracks.bin", 120.).unwrap();
+    /// # let (mut rocket, _tracker) = rust_rocket::simple::mock_rocket(120.);
+    /// rocket.set_missing_track_policy(MissingTrackPolicy::Log);
+    ///
+    /// // Play the production, then report what was missing at exit
+    /// # rocket.get_value("camera:x");
+    /// for name in rocket.get_missing_tracks() {
+    ///     eprintln!("Missing track: {}", name);
+    /// }
+    /// # assert_eq!(rocket.get_missing_tracks().count(), 0);
+    /// # }
+    /// ```
+    pub fn get_missing_tracks(&self) -> impl Iterator<Item = &str> + '_ {
+        #[cfg(not(feature = "player"))]
+        {
+            std::iter::empty()
+        }
+
+        #[cfg(feature = "player")]
+        self.rocket.get_missing_tracks()
+    }
+
+    /// Report tracks that were never read, missing, empty or constant, see [`audit`](crate::audit).
+    ///
+    /// Without the `player` feature the tracker creates missing tracks, so tracks are only reported as missing when
+    /// the tracker isn't connected.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # #[cfg(not(feature = "player"))]
+    /// # {
+    /// # use rust_rocket::simple::{print_msg, Rocket};
+    /// // let mut rocket = Rocket::new("tracks.bin", 120.).unwrap();
+    /// # let (mut rocket, _tracker) = rust_rocket::simple::mock_rocket(120.);
+    ///
+    /// // Play the production, then report stale and forgotten tracks at exit
+    /// # rocket.get_value("camera:x");
+    /// let report = rocket.audit();
+    /// if !report.is_clean() {
+    ///     print_msg("audit", &report.to_string());
+    /// }
+    /// # assert_eq!(report.get_empty_tracks(), ["camera:x"]);
+    /// # }
+    /// ```
+    pub fn audit(&self) -> AuditReport {
+        #[cfg(not(feature = "player"))]
+        {
+            match &self.rocket {
+                Some(rocket) => rocket.audit(),
+                None => AuditReport::new(&[], self.names.iter().map(String::as_str)),
+            }
+        }
+
+        #[cfg(feature = "player")]
+        self.rocket.audit()
     }
 
     /// Update rocket with the current time from your time source, e.g. music player.
@@ -305,7 +494,9 @@ impl<P: AsRef<Path>> Rocket<P> {
     ///
     /// # Example
     ///
-    /// ```rust,no_run
+    /// ```rust
+    /// # #[cfg(not(feature = "player"))]
+    /// # {
     /// # use std::time::Duration;
     /// # use rust_rocket::simple::{Rocket, Event};
     /// # struct MusicPlayer; // Your music player, not included in this crate
@@ -316,7 +507,7 @@ impl<P: AsRef<Path>> Rocket<P> {
     /// #     fn pause(&self, _state: bool) {}
     /// # }
     /// # let music = MusicPlayer::new();
-    /// # let mut rocket = Rocket::new("tracks.bin", 60.).unwrap();
+    /// # let (mut rocket, _tracker) = rust_rocket::simple::mock_rocket(60.);
     /// while let Some(event) = rocket.poll_events().ok().flatten() {
     ///     match event {
     ///         Event::Seek(to) => music.seek(to),
@@ -324,6 +515,7 @@ impl<P: AsRef<Path>> Rocket<P> {
     ///         Event::NotConnected => break,
     ///     }
     /// }
+    /// # }
     /// ```
     ///
     /// # Tips
@@ -342,7 +534,7 @@ impl<P: AsRef<Path>> Rocket<P> {
     /// # With `player` feature
     ///
     /// The function is a no-op.
-    pub fn poll_events(&mut self) -> Result<Option<Event>, EncodeError> {
+    pub fn poll_events(&mut self) -> Result<Option<Event>, io::Error> {
         #[cfg(not(feature = "player"))]
         loop {
             if !self.connected || self.rocket.is_none() {
@@ -351,11 +543,8 @@ impl<P: AsRef<Path>> Rocket<P> {
                     return Ok(Some(Event::NotConnected));
                 }
                 self.connection_attempted = std::time::Instant::now();
-                match Self::connect() {
-                    Ok(rocket) => {
-                        self.rocket = Some(rocket);
-                        self.connected = true;
-                    }
+                match self.connect() {
+                    Ok(()) => self.connected = true,
                     Err(_) => return Ok(Some(Event::NotConnected)),
                 }
             }
@@ -372,10 +561,14 @@ impl<P: AsRef<Path>> Rocket<P> {
                             self.save_tracks()?;
                             continue;
                         }
+                        crate::client::Event::Reconnected
+                        | crate::client::Event::KeySet { .. }
+                        | crate::client::Event::KeyDeleted { .. } => continue,
                     };
                     return Ok(Some(handled));
                 }
                 Some(Ok(None)) => return Ok(None),
+                Some(Err(ref e)) if e.is_recoverable() => print_errors(PREFIX, e),
                 Some(Err(ref e)) => {
                     print_errors(PREFIX, e);
                     self.connected = false;
@@ -402,7 +595,7 @@ impl<P: AsRef<Path>> Rocket<P> {
     /// # With `player` feature
     ///
     /// The function is a no-op.
-    pub fn save_tracks(&self) -> Result<(), EncodeError> {
+    pub fn save_tracks(&self) -> Result<(), io::Error> {
         #[cfg(not(feature = "player"))]
         if let Some(rocket) = &self.rocket {
             let open_result = std::fs::OpenOptions::new()
@@ -411,7 +604,7 @@ impl<P: AsRef<Path>> Rocket<P> {
                 .truncate(true)
                 .open(&self.path);
 
-            let mut file = match open_result {
+            let file = match open_result {
                 Ok(file) => file,
                 Err(e) => {
                     print_msg(
@@ -419,13 +612,14 @@ impl<P: AsRef<Path>> Rocket<P> {
                         &format!("Failed to open {}", self.path.as_ref().display()),
                     );
                     print_errors(PREFIX, &e);
-                    return Err(EncodeError::Io { inner: e, index: 0 });
+                    return Err(e);
                 }
             };
 
             let tracks = rocket.save_tracks();
-            match bincode::encode_into_std_write(tracks, &mut file, bincode::config::standard()) {
-                Ok(_) => {
+            let metadata = file::Metadata::new(self.bpm, ROWS_PER_BEAT as u32);
+            match file::write(io::BufWriter::new(file), tracks, metadata) {
+                Ok(()) => {
                     print_msg(
                         PREFIX,
                         &format!("Tracks saved to {}", self.path.as_ref().display()),
@@ -457,9 +651,20 @@ impl<P: AsRef<Path>> Rocket<P> {
     }
 
     #[cfg(not(feature = "player"))]
-    fn connect() -> Result<crate::RocketClient, crate::client::Error> {
+    fn connect(&mut self) -> Result<(), crate::client::Error> {
         print_msg(PREFIX, "Connecting...");
-        crate::RocketClient::new()
+        match &mut self.rocket {
+            Some(rocket) => rocket.reconnect(),
+            None => {
+                let mut rocket = crate::RocketClient::new()?;
+                // Keep TrackIds valid
+                for name in &self.names {
+                    rocket.get_track_id(name)?;
+                }
+                self.rocket = Some(rocket);
+                Ok(())
+            }
+        }
     }
 }
 
@@ -472,6 +677,7 @@ impl Rocket<&str> {
     /// # Usage
     ///
     /// The function makes it possible to load from e.g. [`std::include_bytes!`] in release builds.
+    /// To compile the tracks into the binary without any decoding, see [`codegen`](crate::codegen) instead.
     ///
     /// ```rust,no_run
     /// # use rust_rocket::simple::Rocket;
@@ -483,9 +689,10 @@ impl Rocket<&str> {
     ///     std::hint::unreachable_unchecked()
     /// });
     /// ```
-    pub fn from_std_read<R: std::io::Read>(read: &mut R, bpm: f32) -> Result<Self, DecodeError> {
-        let tracks = bincode::decode_from_std_read(read, bincode::config::standard())?;
-        let rocket = crate::RocketPlayer::new(tracks);
+    pub fn from_std_read<R: std::io::Read>(read: &mut R, bpm: f32) -> Result<Self, file::Error> {
+        let file = file::read(read)?;
+        check_bpm(&file, bpm);
+        let rocket = crate::RocketPlayer::new(file.into_tracks());
         Ok(Self {
             path: "release",
             bps: bpm / SECS_PER_MINUTE,
@@ -494,3 +701,70 @@ impl Rocket<&str> {
         })
     }
 }
+
+/// A `Rocket` connected to a [`MockTracker`](crate::server::MockTracker), for the examples.
+#[doc(hidden)]
+#[cfg(not(feature = "player"))]
+pub fn mock_rocket(bpm: f32) -> (Rocket<&'static str>, crate::server::MockTracker) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let tracker = std::thread::spawn(move || crate::server::MockTracker::accept(&listener));
+    let client = crate::RocketClient::connect(addr).unwrap();
+    let tracker = tracker.join().unwrap().unwrap();
+    (Rocket::from_client("tracks.bin", client, bpm), tracker)
+}
+
+/// Warn if the tracks were saved with a different tempo, because rows wouldn't match the music.
+#[cfg(feature = "player")]
+fn check_bpm(file: &file::TrackFile, bpm: f32) {
+    if let Some(metadata) = file.get_metadata() {
+        if metadata.get_bpm() != bpm || metadata.get_rows_per_beat() != ROWS_PER_BEAT as u32 {
+            print_msg(
+                PREFIX,
+                &format!(
+                    "Tracks were saved at {} BPM and {} rows per beat, playing at {} BPM and {} rows per beat",
+                    metadata.get_bpm(),
+                    metadata.get_rows_per_beat(),
+                    bpm,
+                    ROWS_PER_BEAT
+                ),
+            );
+        }
+    }
+}
+
+#[cfg(all(test, feature = "player"))]
+mod tests {
+    use super::*;
+    use crate::interpolation::Interpolation;
+    use crate::track::{Key, Track};
+
+    fn test_rocket() -> Rocket<&'static str> {
+        let mut track = Track::new("a");
+        track.set_key(Key::new(0, 1.0, Interpolation::Step));
+        let bytes = file::to_bytes(&[track], file::Metadata::new(120., 8));
+        Rocket::from_std_read(&mut &bytes[..], 120.).unwrap()
+    }
+
+    #[test]
+    fn missing_tracks() {
+        let mut rocket = test_rocket();
+        rocket.set_missing_track_policy(MissingTrackPolicy::Default);
+        rocket.set_default_value("b", 2.0);
+        assert_eq!(rocket.get_value("a"), 1.0);
+        assert_eq!(rocket.get_value("b"), 2.0);
+
+        rocket.set_missing_track_policy(MissingTrackPolicy::Log);
+        let c = rocket.get_track_id("c");
+        assert_eq!(rocket.get_track_id("c"), c);
+        assert_eq!(rocket.get_value_by_id(c), 0.0);
+        assert!(rocket.get_missing_tracks().eq(["b", "c"]));
+        assert_eq!(rocket.audit().get_missing_tracks(), ["b", "c"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't recover")]
+    fn missing_track_panics() {
+        test_rocket().get_value("b");
+    }
+}
@@ -0,0 +1,175 @@
+//! Reading groups of tracks from any source of tracks, [`TrackSource`] and [`RocketTracks`].
+//!
+//! # Usage
+//!
+//! With the `derive` feature, implement [`RocketTracks`] for a struct to map its fields to tracks:
+//!
+//! ```rust
+//! # #[cfg(all(feature = "derive", feature = "std"))]
+//! # fn main() -> Result<(), rust_rocket::client::Error> {
+//! # use rust_rocket::{server::MockTracker, RocketClient, RocketTracks};
+//! #[derive(RocketTracks)]
+//! #[rocket(prefix = "camera")]
+//! struct Camera {
+//!     pos_x: f32,
+//!     pos_y: f32,
+//!     fov: f32,
+//! }
+//!
+//! # let (_tracker, stream) = MockTracker::duplex();
+//! # let mut rocket = RocketClient::from_stream(stream)?;
+//! // let mut rocket = RocketClient::new()?;
+//! // Creates tracks camera:pos_x, camera:pos_y and camera:fov
+//! Camera::register(&mut rocket)?;
+//!
+//! // Every frame, with a client or a player
+//! let camera = Camera::sample(&rocket, 12.5);
+//! println!("fov = {}", camera.fov);
+//! # Ok(())
+//! # }
+//! # #[cfg(not(all(feature = "derive", feature = "std")))]
+//! # fn main() {}
+//! ```
+
+#[cfg(feature = "std")]
+use crate::client::{Error, RocketClient};
+use crate::track::Track;
+use crate::{RocketPlayer, Tracks};
+#[cfg(feature = "std")]
+use std::io::{Read, Write};
+
+/// Anything that tracks can be looked up from by name.
+///
+/// Implemented for [`RocketClient`], [`RocketPlayer`] and [`Tracks`], e.g. a
+/// [`TrackSnapshot`](crate::threaded::TrackSnapshot).
+pub trait TrackSource {
+    /// Get track by name.
+    fn get_track(&self, name: &str) -> Option<&Track>;
+
+    /// Get the value of a track at `row`, or 0 if the track doesn't exist.
+    fn get_value(&self, name: &str, row: f32) -> f32 {
+        self.get_track(name)
+            .map_or(0., |track| track.get_value(row))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<S: Read + Write> TrackSource for RocketClient<S> {
+    fn get_track(&self, name: &str) -> Option<&Track> {
+        RocketClient::get_track(self, name)
+    }
+}
+
+impl TrackSource for RocketPlayer {
+    fn get_track(&self, name: &str) -> Option<&Track> {
+        RocketPlayer::get_track(self, name)
+    }
+}
+
+impl TrackSource for [Track] {
+    fn get_track(&self, name: &str) -> Option<&Track> {
+        self.iter().find(|track| track.get_name() == name)
+    }
+}
+
+impl TrackSource for Tracks {
+    fn get_track(&self, name: &str) -> Option<&Track> {
+        self[..].get_track(name)
+    }
+}
+
+/// A group of tracks, usually a struct with one track per field.
+///
+/// Derive this trait with `#[derive(RocketTracks)]`, which requires the `derive` feature.
+/// Every field becomes a track named `prefix:field`, or `field` if the struct has no `#[rocket(prefix = "...")]`
+/// attribute. Field types must implement `From<f32>`.
+/// See [module-level documentation](crate::source#Usage).
+pub trait RocketTracks: Sized {
+    /// The names of the tracks in the group.
+    const TRACK_NAMES: &'static [&'static str];
+
+    /// Create every track of the group in the client, see [`RocketClient::get_track_mut`].
+    /// Requires the `std` feature.
+    ///
+    /// # Errors
+    ///
+    /// This method can return an [`Error::IOError`] if Rocket tracker disconnects.
+    #[cfg(feature = "std")]
+    fn register<S: Read + Write>(client: &mut RocketClient<S>) -> Result<(), Error> {
+        for name in Self::TRACK_NAMES {
+            client.get_track_mut(name)?;
+        }
+        Ok(())
+    }
+
+    /// Get the values of every track at `row`.
+    ///
+    /// Tracks that don't exist in `source` have the value 0.
+    fn sample<S: TrackSource + ?Sized>(source: &S, row: f32) -> Self;
+}
+
+#[cfg(all(test, feature = "derive", feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::interpolation::Interpolation;
+    use crate::server::MockTracker;
+    use crate::track::Key;
+    use rust_rocket_derive::RocketTracks;
+
+    #[derive(RocketTracks)]
+    #[rocket(prefix = "camera")]
+    struct Camera {
+        pos_x: f32,
+        fov: f64,
+    }
+
+    #[derive(RocketTracks)]
+    struct Unprefixed {
+        r#type: f32,
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(Camera::TRACK_NAMES, ["camera:pos_x", "camera:fov"]);
+        assert_eq!(Unprefixed::TRACK_NAMES, ["type"]);
+    }
+
+    #[test]
+    fn register_and_sample() {
+        let (mut tracker, stream) = MockTracker::duplex();
+        let mut fov = Track::new("camera:fov");
+        fov.set_key(Key::new(0, 90.0, Interpolation::Step));
+        tracker.insert_track(fov);
+
+        let mut rocket = RocketClient::from_stream(stream).unwrap();
+        Camera::register(&mut rocket).unwrap();
+        tracker.poll().unwrap();
+        assert_eq!(tracker.requested_tracks(), Camera::TRACK_NAMES);
+        while rocket.poll_events().unwrap().is_some() {}
+
+        let camera = Camera::sample(&rocket, 1.0);
+        assert_eq!(camera.pos_x, 0.0);
+        assert_eq!(camera.fov, 90.0);
+
+        let player = RocketPlayer::new(rocket.save_tracks().clone());
+        assert_eq!(Camera::sample(&player, 1.0).fov, 90.0);
+        assert_eq!(Camera::sample(rocket.save_tracks(), 1.0).fov, 90.0);
+        assert_eq!(Unprefixed::sample(&player, 1.0).r#type, 0.0);
+    }
+
+    #[test]
+    fn registered_tracks_are_unused_until_sampled() {
+        let (mut tracker, stream) = MockTracker::duplex();
+        let mut rocket = RocketClient::from_stream(stream).unwrap();
+        Camera::register(&mut rocket).unwrap();
+        Unprefixed::register(&mut rocket).unwrap();
+        tracker.poll().unwrap();
+
+        assert_eq!(
+            rocket.audit().get_unused_tracks(),
+            ["camera:pos_x", "camera:fov", "type"]
+        );
+        Camera::sample(&rocket, 1.0);
+        assert_eq!(rocket.audit().get_unused_tracks(), ["type"]);
+    }
+}
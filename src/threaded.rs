@@ -0,0 +1,309 @@
+//! Background thread client, [`RocketThread`].
+//!
+//! [`RocketClient`] needs `&mut self` for both polling and creating tracks, which ties all access to one thread.
+//! [`RocketThread`] moves the client to a dedicated thread that polls the tracker.
+//! Events are delivered over a channel, and track state is published as an immutable [`Arc`] snapshot
+//! which any number of render threads can read through a [`TrackSnapshot`].
+//!
+//! # Usage
+//!
+//! ```rust
+//! # use rust_rocket::{server::MockTracker, threaded::RocketThread, RocketClient};
+//! # let (_tracker, stream) = MockTracker::duplex();
+//! # let rocket = RocketClient::from_stream(stream)?;
+//! // let rocket = RocketClient::new()?;
+//! let rocket = RocketThread::spawn(rocket)?;
+//! rocket.request_track("camera:x");
+//!
+//! let snapshot = rocket.snapshot();
+//! let render_thread = std::thread::spawn(move || {
+//!     // Track creation is asynchronous, so the track may not be in the first snapshots
+//!     let tracks = snapshot.load();
+//!     let value = tracks
+//!         .iter()
+//!         .find(|track| track.get_name() == "camera:x")
+//!         .map_or(0., |track| track.get_value(12.5));
+//!     println!("camera:x = {}", value);
+//! });
+//!
+//! while let Some(event) = rocket.poll_events()? {
+//!     println!("{:?}", event);
+//! }
+//! # render_thread.join().unwrap();
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use crate::client::{Error, Event, RocketClient};
+use crate::Tracks;
+
+use std::{
+    io::{self, Read, Write},
+    net::TcpStream,
+    sync::{
+        mpsc::{self, Receiver, Sender, TryRecvError},
+        Arc, RwLock,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// How long the background thread sleeps when there's nothing to do.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+enum Request {
+    GetTrack(String),
+    SetRow(u32),
+    Stop,
+}
+
+/// A read-only handle to the latest track state of a [`RocketThread`].
+///
+/// The handle can be cloned and sent to other threads.
+#[derive(Debug, Clone, Default)]
+pub struct TrackSnapshot {
+    tracks: Arc<RwLock<Arc<Tracks>>>,
+}
+
+impl TrackSnapshot {
+    /// Get the latest published tracks.
+    ///
+    /// The returned tracks are immutable and never change. Call this again, e.g. once per frame, to see updates.
+    /// The internal lock is only held for the duration of an [`Arc`] clone.
+    pub fn load(&self) -> Arc<Tracks> {
+        let tracks = self.tracks.read().unwrap_or_else(|e| e.into_inner());
+        Arc::clone(&tracks)
+    }
+
+    fn store(&self, tracks: Tracks) {
+        let tracks = Arc::new(tracks);
+        *self.tracks.write().unwrap_or_else(|e| e.into_inner()) = tracks;
+    }
+}
+
+/// A [`RocketClient`] running on a background thread.
+///
+/// See [module-level documentation](crate::threaded#Usage).
+///
+/// Dropping the `RocketThread` asks the background thread to stop, without waiting for it.
+#[derive(Debug)]
+pub struct RocketThread<S = TcpStream> {
+    requests: Sender<Request>,
+    events: Receiver<Result<Event, Error>>,
+    snapshot: TrackSnapshot,
+    handle: Option<JoinHandle<RocketClient<S>>>,
+}
+
+impl<S: Read + Write + Send + 'static> RocketThread<S> {
+    /// Move a connected client to a new background thread.
+    ///
+    /// The client's stream must be in non-blocking mode, like the streams of [`RocketClient::new`] and
+    /// [`RocketClient::connect`]. The background thread only handles requests between calls to
+    /// [`RocketClient::poll_events`], which blocks until the tracker sends something on a blocking stream,
+    /// so [`stop`](Self::stop) would block too. See [`RocketClient::from_stream`].
+    ///
+    /// # Errors
+    ///
+    /// Any error from spawning the thread.
+    pub fn spawn(client: RocketClient<S>) -> io::Result<Self> {
+        let (requests, request_receiver) = mpsc::channel();
+        let (event_sender, events) = mpsc::channel();
+        let snapshot = TrackSnapshot::default();
+        snapshot.store(client.save_tracks().clone());
+
+        let thread_snapshot = snapshot.clone();
+        let handle = thread::Builder::new()
+            .name(String::from("rocket"))
+            .spawn(move || run(client, request_receiver, event_sender, thread_snapshot))?;
+
+        Ok(Self {
+            requests,
+            events,
+            snapshot,
+            handle: Some(handle),
+        })
+    }
+
+    /// Get a handle for reading the latest track state from any thread.
+    pub fn snapshot(&self) -> TrackSnapshot {
+        self.snapshot.clone()
+    }
+
+    /// Ask the background thread to create a track, see [`RocketClient::get_track_mut`].
+    ///
+    /// The track appears in a later snapshot.
+    pub fn request_track(&self, name: &str) {
+        let _ = self.requests.send(Request::GetTrack(String::from(name)));
+    }
+
+    /// Ask the background thread to send a SetRow message, see [`RocketClient::set_row`].
+    pub fn set_row(&self, row: u32) {
+        let _ = self.requests.send(Request::SetRow(row));
+    }
+
+    /// Poll for new events from the background thread.
+    ///
+    /// It is recommended to keep calling this as long as your receive `Some(Event)`.
+    ///
+    /// # Errors
+    ///
+    /// Recoverable protocol errors (see [`Error::is_recoverable`]) are forwarded and the thread keeps running.
+    /// Otherwise, the error that stopped the background thread, e.g. [`Error::IOError`] if the rocket tracker disconnects.
+    /// After that, every call returns an [`Error::IOError`] with [`io::ErrorKind::NotConnected`].
+    pub fn poll_events(&self) -> Result<Option<Event>, Error> {
+        match self.events.try_recv() {
+            Ok(result) => result.map(Some),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(Error::IOError(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "Rocket client thread has stopped",
+            ))),
+        }
+    }
+
+    /// Stop the background thread and get the client back.
+    ///
+    /// This waits for the background thread to handle the request, see [`spawn`](Self::spawn).
+    ///
+    /// # Panics
+    ///
+    /// If the background thread panicked.
+    pub fn stop(mut self) -> RocketClient<S> {
+        let _ = self.requests.send(Request::Stop);
+        let handle = self.handle.take().unwrap_or_else(|| unreachable!());
+        handle
+            .join()
+            .unwrap_or_else(|e| std::panic::resume_unwind(e))
+    }
+}
+
+impl<S> Drop for RocketThread<S> {
+    fn drop(&mut self) {
+        // Not joined, so dropping never blocks, even if the thread is stuck reading a blocking stream
+        if self.handle.take().is_some() {
+            let _ = self.requests.send(Request::Stop);
+        }
+    }
+}
+
+fn run<S: Read + Write>(
+    mut client: RocketClient<S>,
+    requests: Receiver<Request>,
+    events: Sender<Result<Event, Error>>,
+    snapshot: TrackSnapshot,
+) -> RocketClient<S> {
+    let mut published = client.generation();
+    loop {
+        let result = step(&mut client, &requests, &events);
+
+        if client.generation() != published {
+            published = client.generation();
+            snapshot.store(client.save_tracks().clone());
+        }
+
+        match result {
+            Ok(Step::Busy) => {}
+            Ok(Step::Idle) => thread::sleep(POLL_INTERVAL),
+            Ok(Step::Stop) => return client,
+            Err(e) => {
+                let _ = events.send(Err(e));
+                return client;
+            }
+        }
+    }
+}
+
+enum Step {
+    Busy,
+    Idle,
+    Stop,
+}
+
+/// Handle pending requests and forward events from the tracker.
+fn step<S: Read + Write>(
+    client: &mut RocketClient<S>,
+    requests: &Receiver<Request>,
+    events: &Sender<Result<Event, Error>>,
+) -> Result<Step, Error> {
+    let mut step = Step::Idle;
+    loop {
+        match requests.try_recv() {
+            Ok(Request::GetTrack(name)) => {
+                client.get_track_mut(&name)?;
+            }
+            Ok(Request::SetRow(row)) => client.set_row(row)?,
+            Ok(Request::Stop) | Err(TryRecvError::Disconnected) => return Ok(Step::Stop),
+            Err(TryRecvError::Empty) => break,
+        }
+        step = Step::Busy;
+    }
+    loop {
+        match client.poll_events() {
+            Ok(Some(event)) => {
+                let _ = events.send(Ok(event));
+            }
+            Ok(None) => return Ok(step),
+            Err(e) if e.is_recoverable() => {
+                let _ = events.send(Err(e));
+            }
+            Err(e) => return Err(e),
+        }
+        step = Step::Busy;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpolation::Interpolation;
+    use crate::server::{tests::wait_until, MockTracker};
+    use crate::track::Key;
+
+    #[test]
+    fn snapshots_and_events() {
+        let (mut tracker, stream) = MockTracker::duplex();
+        let rocket = RocketThread::spawn(RocketClient::from_stream(stream).unwrap()).unwrap();
+        let snapshot = rocket.snapshot();
+        assert!(snapshot.load().is_empty());
+
+        rocket.request_track("a");
+        rocket.set_row(5);
+        wait_until(|| {
+            tracker.poll().unwrap();
+            tracker.row().is_some()
+        });
+        assert_eq!(tracker.requested_tracks(), ["a"]);
+        wait_until(|| snapshot.load().len() == 1);
+
+        let before = snapshot.load();
+        let key = Key::new(1, 3.0, Interpolation::Step);
+        tracker.set_key("a", key).unwrap();
+        tracker.pause(true).unwrap();
+        wait_until(|| snapshot.load()[0].key_at(1).is_some());
+        assert!(before[0].keys().is_empty());
+
+        let mut event = None;
+        wait_until(|| {
+            event = rocket.poll_events().unwrap();
+            event.is_some()
+        });
+        assert!(matches!(event, Some(Event::Pause(true))));
+
+        let client = rocket.stop();
+        assert_eq!(client.get_track("a").unwrap().keys(), [key]);
+    }
+
+    #[test]
+    fn disconnect() {
+        let (tracker, stream) = MockTracker::duplex();
+        let rocket = RocketThread::spawn(RocketClient::from_stream(stream).unwrap()).unwrap();
+        drop(tracker);
+
+        let mut result = Ok(None);
+        wait_until(|| {
+            result = rocket.poll_events();
+            result.is_err()
+        });
+        assert!(matches!(result, Err(Error::IOError(_))));
+        assert!(rocket.poll_events().is_err());
+    }
+}
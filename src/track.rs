@@ -1,11 +1,13 @@
 //! [`Key`] and [`Track`] types.
 
 use crate::interpolation::*;
+use alloc::{string::String, vec::Vec};
+use core::ops::{Bound, RangeBounds};
 
 /// The `Key` Type.
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Key {
     row: u32,
     value: f32,
@@ -21,12 +23,50 @@ impl Key {
             interpolation: interp,
         }
     }
+
+    /// Get the row of the key.
+    pub fn get_row(&self) -> u32 {
+        self.row
+    }
+
+    /// Get the value of the key.
+    pub fn get_value(&self) -> f32 {
+        self.value
+    }
+
+    /// Get the interpolation used between this key and the next one.
+    pub fn get_interpolation(&self) -> Interpolation {
+        self.interpolation
+    }
+}
+
+/// A handle to a track, for lookups without comparing names.
+///
+/// Get one from [`RocketClient::get_track_id`](crate::RocketClient::get_track_id),
+/// [`RocketPlayer::get_track_id`](crate::RocketPlayer::get_track_id) or
+/// [`simple::Rocket::get_track_id`](crate::simple::Rocket::get_track_id), and only use it with the same object.
+///
+/// The handle is the position of the track in [`Tracks`](crate::Tracks). A client keeps the positions across reconnects,
+/// and [`RocketClient::save_tracks`](crate::RocketClient::save_tracks) saves the tracks in the same order,
+/// so a player loaded from the saved tracks gives out the same handles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TrackId(usize);
+
+impl TrackId {
+    pub(crate) fn new(index: usize) -> Self {
+        Self(index)
+    }
+
+    /// Get the position of the track in [`Tracks`](crate::Tracks).
+    pub fn index(self) -> usize {
+        self.0
+    }
 }
 
 /// The `Track` Type. This is a collection of `Key`s with a name.
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Track {
     name: String,
     keys: Vec<Key>,
@@ -46,33 +86,103 @@ impl Track {
         self.name.as_str()
     }
 
+    /// Get all keys of the track, sorted by row.
+    pub fn keys(&self) -> &[Key] {
+        &self.keys
+    }
+
+    /// Iterate over the segments of the track.
+    ///
+    /// Each segment is a pair of consecutive keys `(lower, upper)`.
+    /// Values between the two keys are interpolated using `lower`'s [`Interpolation`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_rocket::{interpolation::Interpolation, track::{Key, Track}};
+    /// let mut track = Track::new("test");
+    /// track.set_key(Key::new(0, 0.0, Interpolation::Linear));
+    /// track.set_key(Key::new(8, 1.0, Interpolation::Step));
+    /// track.set_key(Key::new(16, 0.0, Interpolation::Step));
+    ///
+    /// for (lower, upper) in track.segments() {
+    ///     println!(
+    ///         "{}..{}: {:?}",
+    ///         lower.get_row(),
+    ///         upper.get_row(),
+    ///         lower.get_interpolation()
+    ///     );
+    /// }
+    /// assert_eq!(track.segments().count(), 2);
+    /// ```
+    pub fn segments(&self) -> impl Iterator<Item = (&Key, &Key)> + '_ {
+        self.keys.windows(2).map(|pair| (&pair[0], &pair[1]))
+    }
+
+    /// Get the key at exactly `row`, if there is one.
+    pub fn key_at(&self, row: u32) -> Option<&Key> {
+        self.get_exact_position(row).map(|pos| &self.keys[pos])
+    }
+
+    /// Get the keys whose rows are within `range`, sorted by row.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_rocket::{interpolation::Interpolation, track::{Key, Track}};
+    /// let mut track = Track::new("test");
+    /// for row in [0, 4, 8, 12] {
+    ///     track.set_key(Key::new(row, row as f32, Interpolation::Step));
+    /// }
+    /// let rows: Vec<u32> = track.keys_in_range(4..12).iter().map(Key::get_row).collect();
+    /// assert_eq!(rows, [4, 8]);
+    /// ```
+    pub fn keys_in_range(&self, range: impl RangeBounds<u32>) -> &[Key] {
+        let start = match range.start_bound() {
+            Bound::Included(&row) => self.get_insert_position(row),
+            Bound::Excluded(&row) => self.get_upper_bound_position(row),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&row) => self.get_upper_bound_position(row),
+            Bound::Excluded(&row) => self.get_insert_position(row),
+            Bound::Unbounded => self.keys.len(),
+        };
+
+        if start < end {
+            &self.keys[start..end]
+        } else {
+            &[]
+        }
+    }
+
     fn get_exact_position(&self, row: u32) -> Option<usize> {
-        self.keys.iter().position(|k| k.row == row)
+        self.keys.binary_search_by_key(&row, |k| k.row).ok()
     }
 
-    fn get_insert_position(&self, row: u32) -> Option<usize> {
-        self.keys.iter().position(|k| k.row >= row)
+    /// Position of the first key with `key.row >= row`, or `keys.len()`.
+    fn get_insert_position(&self, row: u32) -> usize {
+        self.keys.partition_point(|k| k.row < row)
     }
 
-    fn get_lower_bound_position(&self, row: u32) -> usize {
-        self.keys
-            .iter()
-            .position(|k| k.row > row)
-            .unwrap_or(self.keys.len())
-            - 1
+    /// Position of the first key with `key.row > row`, or `keys.len()`.
+    fn get_upper_bound_position(&self, row: u32) -> usize {
+        self.keys.partition_point(|k| k.row <= row)
     }
 
     /// Insert or update a key on a track.
     pub fn set_key(&mut self, key: Key) {
-        if let Some(pos) = self.get_exact_position(key.row) {
-            self.keys[pos] = key;
-        } else if let Some(pos) = self.get_insert_position(key.row) {
-            self.keys.insert(pos, key);
-        } else {
-            self.keys.push(key);
+        match self.keys.binary_search_by_key(&key.row, |k| k.row) {
+            Ok(pos) => self.keys[pos] = key,
+            Err(pos) => self.keys.insert(pos, key),
         }
     }
 
+    #[cfg(feature = "std")]
+    pub(crate) fn clear_keys(&mut self) {
+        self.keys.clear();
+    }
+
     /// Delete a key from a track.
     ///
     /// If a key does not exist this will do nothing.
@@ -86,37 +196,185 @@ impl Track {
     ///
     /// The row can be between two integers.
     /// This will perform the required interpolation.
+    ///
+    /// The lookup is a binary search over the keys.
+    /// If you evaluate a track at steadily increasing rows, e.g. during playback,
+    /// [`TrackCursor::get_value`] avoids the search in most cases.
     pub fn get_value(&self, row: f32) -> f32 {
-        if self.keys.is_empty() {
-            return 0.0;
+        self.keys[..].get_value(row)
+    }
+}
+
+/// Keys sorted by row, evaluated like a [`Track`].
+///
+/// Implemented for the keys of a [`Track`], and for the keys of a
+/// [`TrackView`](crate::view::TrackView) which are read from bytes on every access.
+pub(crate) trait SortedKeys {
+    /// The number of keys.
+    fn count(&self) -> usize;
+
+    /// Get the key at `pos`, which must be less than [`count`](Self::count).
+    fn key(&self, pos: usize) -> Key;
+
+    /// Position of the first key with `key.row > row`, or [`count`](Self::count).
+    fn upper_bound_position(&self, row: u32) -> usize {
+        let (mut low, mut high) = (0, self.count());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.key(mid).row <= row {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
         }
+        low
+    }
 
-        let lower_row = row.floor() as u32;
+    fn get_value(&self, row: f32) -> f32 {
+        match self.get_clamped_value(row) {
+            Ok(value) => value,
+            Err(lower_row) => {
+                let pos = self.upper_bound_position(lower_row) - 1;
+                self.interpolate_segment(pos, row)
+            }
+        }
+    }
 
-        if lower_row <= self.keys[0].row {
-            return self.keys[0].value;
+    /// Returns `Ok` if `row` is outside of the keys, or the floored row otherwise.
+    fn get_clamped_value(&self, row: f32) -> Result<f32, u32> {
+        let count = self.count();
+        if count == 0 {
+            return Ok(0.0);
         }
 
-        if lower_row >= self.keys[self.keys.len() - 1].row {
-            return self.keys[self.keys.len() - 1].value;
+        // Negative rows and NaN saturate to 0, so truncating is the same as flooring
+        let lower_row = row as u32;
+
+        let first = self.key(0);
+        if lower_row <= first.row {
+            return Ok(first.value);
         }
 
-        let pos = self.get_lower_bound_position(lower_row);
+        let last = self.key(count - 1);
+        if lower_row >= last.row {
+            return Ok(last.value);
+        }
+
+        Err(lower_row)
+    }
 
-        let lower = &self.keys[pos];
-        let higher = &self.keys[pos + 1];
+    fn interpolate_segment(&self, pos: usize, row: f32) -> f32 {
+        let lower = self.key(pos);
+        let higher = self.key(pos + 1);
 
         let t = (row - (lower.row as f32)) / ((higher.row as f32) - (lower.row as f32));
         let it = lower.interpolation.interpolate(t);
 
         lower.value + (higher.value - lower.value) * it
     }
+
+    /// Returns true if `pos` is the segment containing `row`.
+    fn segment_contains(&self, pos: usize, row: u32) -> bool {
+        pos + 1 < self.count() && self.key(pos).row <= row && row < self.key(pos + 1).row
+    }
+}
+
+impl SortedKeys for [Key] {
+    fn count(&self) -> usize {
+        self.len()
+    }
+
+    fn key(&self, pos: usize) -> Key {
+        self[pos]
+    }
+
+    fn upper_bound_position(&self, row: u32) -> usize {
+        self.partition_point(|k| k.row <= row)
+    }
+}
+
+/// A cursor for evaluating a [`Track`] during sequential playback.
+///
+/// The cursor remembers the segment used by the previous lookup. When the next row is in the same or
+/// the following segment, the value is computed without searching, which makes forward playback amortised O(1).
+/// Seeking backwards or far ahead falls back to a binary search.
+///
+/// A cursor is not tied to a single track, but it's only useful when it's used with one track.
+/// Editing the track's keys is fine, the cached segment is validated on every lookup.
+///
+/// # Examples
+///
+/// ```
+/// # use rust_rocket::{interpolation::Interpolation, track::{Key, Track, TrackCursor}};
+/// let mut track = Track::new("test");
+/// track.set_key(Key::new(0, 0.0, Interpolation::Linear));
+/// track.set_key(Key::new(10, 1.0, Interpolation::Linear));
+///
+/// let mut cursor = TrackCursor::new();
+/// for row in 0..20 {
+///     let row = row as f32;
+///     assert_eq!(cursor.get_value(&track, row), track.get_value(row));
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TrackCursor {
+    pos: usize,
+}
+
+impl TrackCursor {
+    /// Construct a new `TrackCursor`.
+    pub fn new() -> TrackCursor {
+        TrackCursor::default()
+    }
+
+    /// Get a value from `track` based on a row.
+    ///
+    /// Returns the same value as [`Track::get_value`].
+    pub fn get_value(&mut self, track: &Track, row: f32) -> f32 {
+        let keys = &track.keys[..];
+        match keys.get_clamped_value(row) {
+            Ok(value) => value,
+            Err(lower_row) => {
+                if !keys.segment_contains(self.pos, lower_row) {
+                    if keys.segment_contains(self.pos + 1, lower_row) {
+                        self.pos += 1;
+                    } else {
+                        self.pos = keys.upper_bound_position(lower_row) - 1;
+                    }
+                }
+                keys.interpolate_segment(self.pos, row)
+            }
+        }
+    }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
 
+    /// A project with every interpolation, and keys both close together and far apart.
+    #[cfg(feature = "std")] // Only used by tests of std-only modules
+    pub(crate) fn sample_project() -> Vec<Track> {
+        let mut camera = Track::new("camera:x");
+        let mut fov = Track::new("camera:fov");
+        for i in 0..24u32 {
+            let interpolation = Interpolation::from((i % 4) as u8);
+            camera.set_key(Key::new(
+                i * i,
+                (i as f32 * 0.37).sin() * 10.,
+                interpolation,
+            ));
+            fov.set_key(Key::new(
+                5 + i * 3,
+                45. - i as f32 / 3.,
+                Interpolation::Smooth,
+            ));
+        }
+        let mut single = Track::new("single");
+        single.set_key(Key::new(8, 1e-7, Interpolation::Linear));
+        vec![camera, fov, single, Track::new("empty")]
+    }
+
     fn test_track() -> Track {
         let mut track = Track::new("test");
         track.set_key(Key::new(0, 1.0, Interpolation::Step));
@@ -142,12 +400,79 @@ mod tests {
         assert_eq!(track.get_value(21.), 2.0);
     }
 
+    #[test]
+    fn test_set_key_keeps_order() {
+        let mut track = Track::new("test");
+        for row in [8, 2, 6, 4, 0, 6] {
+            track.set_key(Key::new(row, row as f32, Interpolation::Step));
+        }
+        let rows: Vec<u32> = track.keys().iter().map(Key::get_row).collect();
+        assert_eq!(rows, [0, 2, 4, 6, 8]);
+
+        track.delete_key(4);
+        track.delete_key(5);
+        let rows: Vec<u32> = track.keys().iter().map(Key::get_row).collect();
+        assert_eq!(rows, [0, 2, 6, 8]);
+    }
+
     #[test]
     fn test_keys() {
         let track = test_track();
         assert_test_track(&track);
     }
 
+    #[test]
+    fn test_key_access() {
+        let track = test_track();
+        let rows: Vec<u32> = track.keys().iter().map(Key::get_row).collect();
+        assert_eq!(rows, [0, 5, 10, 20]);
+
+        let key = track.key_at(10).unwrap();
+        assert_eq!(key.get_value(), 1.0);
+        assert_eq!(key.get_interpolation(), Interpolation::Linear);
+        assert!(track.key_at(11).is_none());
+
+        let segments: Vec<(u32, u32)> = track
+            .segments()
+            .map(|(lower, upper)| (lower.get_row(), upper.get_row()))
+            .collect();
+        assert_eq!(segments, [(0, 5), (5, 10), (10, 20)]);
+    }
+
+    #[test]
+    fn test_keys_in_range() {
+        let track = test_track();
+        let rows = |keys: &[Key]| keys.iter().map(Key::get_row).collect::<Vec<_>>();
+        assert_eq!(rows(track.keys_in_range(..)), [0, 5, 10, 20]);
+        assert_eq!(rows(track.keys_in_range(5..20)), [5, 10]);
+        assert_eq!(rows(track.keys_in_range(5..=20)), [5, 10, 20]);
+        assert_eq!(rows(track.keys_in_range(6..)), [10, 20]);
+        assert_eq!(rows(track.keys_in_range(..=4)), [0]);
+        assert!(track.keys_in_range(21..).is_empty());
+        assert!(track.keys_in_range(6..9).is_empty());
+        #[allow(clippy::reversed_empty_ranges)]
+        let reversed = track.keys_in_range(10..5);
+        assert!(reversed.is_empty());
+    }
+
+    #[test]
+    fn test_cursor() {
+        let mut track = test_track();
+        let mut cursor = TrackCursor::new();
+
+        for row in (-10..250).chain(100..150).chain(0..10) {
+            let row = row as f32 / 10.;
+            assert_eq!(cursor.get_value(&track, row), track.get_value(row));
+        }
+
+        track.delete_key(10);
+        track.set_key(Key::new(12, 4.0, Interpolation::Smooth));
+        for row in 0..250 {
+            let row = row as f32 / 10.;
+            assert_eq!(cursor.get_value(&track, row), track.get_value(row));
+        }
+    }
+
     #[test]
     #[cfg(feature = "bincode")]
     fn test_bincode_roundtrip() {
@@ -0,0 +1,464 @@
+//! Playing tracks directly from the bytes of a track file, [`PlayerView`].
+//!
+//! A [`PlayerView`] borrows a track file, e.g. from [`include_bytes!`] or a memory-mapped file, and evaluates tracks
+//! from it without decoding or allocating anything. Constructing one validates the whole file once, so lookups and
+//! evaluation afterwards can't fail. Works without the `std` feature.
+//!
+//! # Layout
+//!
+//! A track file starts with a header, followed by the tracks:
+//!
+//! | Size | Content                                                        |
+//! | ---  | ---                                                            |
+//! | 8    | Magic bytes `RKTRACKS`                                         |
+//! | 2    | Format version, currently 1                                    |
+//! | 2    | Reserved, 0                                                    |
+//! | 4    | BPM, 32-bit float                                              |
+//! | 4    | Rows per beat                                                  |
+//! | 4    | Length of the tracks in bytes                                  |
+//! | 4    | CRC-32 (IEEE) checksum of the tracks                           |
+//! | ...  | Number of tracks, then each track's name length, name and keys |
+//!
+//! Numbers are little-endian. Each track has a 32-bit name length, the UTF-8 name, a 32-bit number of keys,
+//! and each key's row (32-bit integer), value (32-bit float) and interpolation (8-bit integer).
+//! Keys are sorted by row, and no two keys of a track have the same row.
+//! Nothing is aligned, so the data can be at any address.
+//!
+//! Write track files with [`file::write`](crate::file::write), which needs the `std` feature.
+//!
+//! # Usage
+//!
+//! ```rust
+//! # // Writing the example file needs std
+//! # #[cfg(feature = "std")]
+//! # {
+//! # use rust_rocket::{file::{self, Metadata}, view::PlayerView, Track};
+//! # let bytes = file::to_bytes(&[Track::new("camera:x")], Metadata::new(120., 8));
+//! # #[allow(non_snake_case)]
+//! # let SYNC_DATA: &[u8] = &bytes;
+//! // static SYNC_DATA: &[u8] = include_bytes!("tracks.bin");
+//! let player = PlayerView::new(SYNC_DATA).expect("Invalid sync data");
+//!
+//! // Look up tracks once, views are cheap to copy and keep around
+//! let camera_x = player.get_track("camera:x").expect("Missing track camera:x");
+//! println!("Value at row 123: {}", camera_x.get_value(123.));
+//! # }
+//! ```
+
+use crate::interpolation::Interpolation;
+use crate::track::{Key, SortedKeys, Track, TrackId};
+use crate::Tracks;
+
+use byteorder::{ByteOrder, LittleEndian};
+use core::{convert::TryFrom, str};
+use thiserror::Error;
+
+/// The magic bytes at the start of every track file.
+pub const MAGIC: [u8; 8] = *b"RKTRACKS";
+/// The version of the format written by this crate.
+pub const FORMAT_VERSION: u16 = 1;
+
+pub(crate) const HEADER_LEN: usize = 28;
+pub(crate) const KEY_LEN: usize = 9;
+
+/// The `Error` Type for validating track files.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The data doesn't start with [`MAGIC`]
+    #[error("Not a track file")]
+    NotATrackFile,
+    /// The file has a version other than [`FORMAT_VERSION`]
+    #[error("Unsupported track file version {0}, expected version {FORMAT_VERSION}")]
+    UnsupportedVersion(u16),
+    /// The file has been modified or truncated after writing
+    #[error("Track file checksum {actual:08x} doesn't match {expected:08x}, the file is corrupt")]
+    ChecksumMismatch {
+        /// The checksum in the header
+        expected: u32,
+        /// The checksum of the data
+        actual: u32,
+    },
+    /// The file has a valid checksum, but invalid contents
+    #[error("Track file is corrupt: {0}")]
+    Corrupt(&'static str),
+}
+
+/// Information about the production that the tracks belong to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Metadata {
+    bpm: f32,
+    rows_per_beat: u32,
+}
+
+impl Metadata {
+    /// Construct new `Metadata`.
+    pub fn new(bpm: f32, rows_per_beat: u32) -> Self {
+        Self { bpm, rows_per_beat }
+    }
+
+    /// Get the tempo in beats per minute.
+    pub fn get_bpm(&self) -> f32 {
+        self.bpm
+    }
+
+    /// Get the number of rows per beat.
+    pub fn get_rows_per_beat(&self) -> u32 {
+        self.rows_per_beat
+    }
+}
+
+/// A player for the tracks in a track file, borrowing the file's bytes.
+///
+/// Looking up a track by name or [`TrackId`] scans the tracks, so look up tracks once and keep the [`TrackView`]s.
+#[derive(Debug, Clone, Copy)]
+pub struct PlayerView<'a> {
+    metadata: Metadata,
+    len: usize,
+    /// The tracks after the number of tracks
+    tracks: &'a [u8],
+}
+
+impl<'a> PlayerView<'a> {
+    /// Validates a track file and constructs a `PlayerView` of it.
+    ///
+    /// # Errors
+    ///
+    /// An [`Error`](enum@Error) if the file is corrupt or has another version than [`FORMAT_VERSION`].
+    /// Use [`file::read`](crate::file::read) for files from older versions of this crate.
+    pub fn new(bytes: &'a [u8]) -> Result<Self, Error> {
+        if !bytes.starts_with(&MAGIC) {
+            return Err(Error::NotATrackFile);
+        }
+        if bytes.len() < HEADER_LEN {
+            return Err(Error::Corrupt("truncated header"));
+        }
+
+        let version = LittleEndian::read_u16(&bytes[8..10]);
+        if version != FORMAT_VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        let metadata = Metadata::new(
+            LittleEndian::read_f32(&bytes[12..16]),
+            LittleEndian::read_u32(&bytes[16..20]),
+        );
+        let len = LittleEndian::read_u32(&bytes[20..24]);
+        let expected = LittleEndian::read_u32(&bytes[24..28]);
+
+        let mut payload = &bytes[HEADER_LEN..];
+        let actual = crc32(payload);
+        if actual != expected {
+            return Err(Error::ChecksumMismatch { expected, actual });
+        }
+        if usize::try_from(len).ok() != Some(payload.len()) {
+            return Err(Error::Corrupt("length doesn't match the header"));
+        }
+
+        let num_tracks = take_u32(&mut payload)? as usize;
+        let tracks = payload;
+        for _ in 0..num_tracks {
+            let track = take_track(&mut payload)?;
+            for (lower, higher) in track.keys().zip(track.keys().skip(1)) {
+                if lower.get_row() >= higher.get_row() {
+                    return Err(Error::Corrupt("keys aren't sorted by row"));
+                }
+            }
+            if track
+                .raw_keys()
+                .any(|key| Interpolation::from_u8(key[8]).is_none())
+            {
+                return Err(Error::Corrupt("unknown interpolation"));
+            }
+        }
+        if !payload.is_empty() {
+            return Err(Error::Corrupt("trailing data after the tracks"));
+        }
+
+        Ok(Self {
+            metadata,
+            len: num_tracks,
+            tracks,
+        })
+    }
+
+    /// Get the metadata of the file.
+    pub fn get_metadata(&self) -> Metadata {
+        self.metadata
+    }
+
+    /// Get the number of tracks.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if there are no tracks.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterate over the tracks, in the order of the file.
+    pub fn tracks(&self) -> TrackViews<'a> {
+        TrackViews {
+            remaining: self.len,
+            tracks: self.tracks,
+        }
+    }
+
+    /// Get track by name.
+    pub fn get_track(&self, name: &str) -> Option<TrackView<'a>> {
+        self.tracks().find(|track| track.get_name() == name)
+    }
+
+    /// Get a handle to a track by name, for [`get_track_by_id`](Self::get_track_by_id).
+    ///
+    /// The handle is the position of the track, the same as [`RocketPlayer::get_track_id`](crate::RocketPlayer::get_track_id)
+    /// gives for a player loaded from the same file.
+    pub fn get_track_id(&self, name: &str) -> Option<TrackId> {
+        self.tracks()
+            .position(|track| track.get_name() == name)
+            .map(TrackId::new)
+    }
+
+    /// Get track by a handle from [`get_track_id`](Self::get_track_id).
+    pub fn get_track_by_id(&self, id: TrackId) -> Option<TrackView<'a>> {
+        self.tracks().nth(id.index())
+    }
+
+    /// Decode all tracks, e.g. for [`RocketPlayer::new`](crate::RocketPlayer::new).
+    pub fn to_tracks(&self) -> Tracks {
+        self.tracks().map(|track| track.to_track()).collect()
+    }
+}
+
+/// An iterator over the tracks of a [`PlayerView`], returned by [`PlayerView::tracks`].
+#[derive(Debug, Clone)]
+pub struct TrackViews<'a> {
+    remaining: usize,
+    tracks: &'a [u8],
+}
+
+impl<'a> Iterator for TrackViews<'a> {
+    type Item = TrackView<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        // The tracks have been validated, so this can't fail
+        take_track(&mut self.tracks).ok()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for TrackViews<'_> {}
+
+/// A track in a [`PlayerView`], evaluated like a [`Track`].
+#[derive(Debug, Clone, Copy)]
+pub struct TrackView<'a> {
+    name: &'a str,
+    keys: &'a [u8],
+}
+
+impl<'a> TrackView<'a> {
+    /// Get the name of the track.
+    pub fn get_name(&self) -> &'a str {
+        self.name
+    }
+
+    /// Iterate over the keys of the track, sorted by row.
+    pub fn keys(&self) -> impl ExactSizeIterator<Item = Key> + 'a {
+        self.raw_keys().map(read_key)
+    }
+
+    /// Get a value based on a row, the same as [`Track::get_value`].
+    ///
+    /// The lookup is a binary search over the keys.
+    pub fn get_value(&self, row: f32) -> f32 {
+        SortedKeys::get_value(self, row)
+    }
+
+    /// Decode the track.
+    pub fn to_track(&self) -> Track {
+        let mut track = Track::new(self.name);
+        for key in self.keys() {
+            track.set_key(key);
+        }
+        track
+    }
+
+    fn raw_keys(&self) -> impl ExactSizeIterator<Item = &'a [u8]> + 'a {
+        self.keys.chunks_exact(KEY_LEN)
+    }
+}
+
+impl SortedKeys for TrackView<'_> {
+    fn count(&self) -> usize {
+        self.keys.len() / KEY_LEN
+    }
+
+    fn key(&self, pos: usize) -> Key {
+        read_key(&self.keys[pos * KEY_LEN..(pos + 1) * KEY_LEN])
+    }
+}
+
+fn read_key(key: &[u8]) -> Key {
+    Key::new(
+        LittleEndian::read_u32(&key[0..4]),
+        LittleEndian::read_f32(&key[4..8]),
+        Interpolation::from(key[8]),
+    )
+}
+
+fn take_track<'a>(payload: &mut &'a [u8]) -> Result<TrackView<'a>, Error> {
+    let name_len = take_u32(payload)? as usize;
+    let name = take(payload, name_len)?;
+    let name = str::from_utf8(name).map_err(|_| Error::Corrupt("track name isn't UTF-8"))?;
+    let num_keys = take_u32(payload)? as usize;
+    let keys = take(payload, num_keys.saturating_mul(KEY_LEN))?;
+    Ok(TrackView { name, keys })
+}
+
+fn take<'a>(payload: &mut &'a [u8], len: usize) -> Result<&'a [u8], Error> {
+    if payload.len() < len {
+        return Err(Error::Corrupt("truncated tracks"));
+    }
+    let (head, tail) = payload.split_at(len);
+    *payload = tail;
+    Ok(head)
+}
+
+fn take_u32(payload: &mut &[u8]) -> Result<u32, Error> {
+    take(payload, 4).map(LittleEndian::read_u32)
+}
+
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// CRC-32 (IEEE 802.3), the checksum of zip and PNG.
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc, &byte| {
+        CRC32_TABLE[usize::from(crc as u8 ^ byte)] ^ (crc >> 8)
+    })
+}
+
+// Writing test files needs std
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::file::{self, from_bytes};
+    use crate::track::tests::sample_project;
+
+    /// Rewrite the checksum after modifying the tracks.
+    fn fix_checksum(bytes: &mut [u8]) {
+        let checksum = crc32(&bytes[HEADER_LEN..]);
+        bytes[24..28].copy_from_slice(&checksum.to_le_bytes());
+    }
+
+    #[test]
+    fn view_matches_tracks() {
+        let metadata = Metadata::new(128., 4);
+        let bytes = file::to_bytes(&sample_project(), metadata);
+        let view = PlayerView::new(&bytes).unwrap();
+        assert_eq!(view.get_metadata(), metadata);
+        assert_eq!(view.len(), 4);
+        assert_eq!(view.tracks().len(), 4);
+        assert_eq!(view.to_tracks(), sample_project());
+
+        for (track, expected) in view.tracks().zip(sample_project()) {
+            assert_eq!(track.get_name(), expected.get_name());
+            assert!(track.keys().eq(expected.keys().iter().copied()));
+            for row in (-8..=2400)
+                .map(|row| row as f32 / 4.)
+                .chain([f32::NAN, 1e9])
+            {
+                assert_eq!(
+                    track.get_value(row).to_bits(),
+                    expected.get_value(row).to_bits(),
+                    "{} at row {}",
+                    track.get_name(),
+                    row
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn lookup() {
+        let tracks = sample_project();
+        let bytes = file::to_bytes(&tracks, Metadata::new(120., 8));
+        let view = PlayerView::new(&bytes).unwrap();
+        let player = crate::RocketPlayer::new(tracks);
+
+        for name in ["camera:x", "camera:fov", "single", "empty"] {
+            let id = view.get_track_id(name).unwrap();
+            assert_eq!(Some(id), player.get_track_id(name));
+            assert_eq!(view.get_track_by_id(id).unwrap().get_name(), name);
+            assert_eq!(view.get_track(name).unwrap().get_name(), name);
+        }
+        assert!(view.get_track("missing").is_none());
+        assert!(view.get_track_by_id(TrackId::new(4)).is_none());
+
+        let empty = file::to_bytes(&[], Metadata::new(120., 8));
+        assert!(PlayerView::new(&empty).unwrap().is_empty());
+    }
+
+    #[test]
+    fn invalid() {
+        let mut bytes = file::to_bytes(&sample_project(), Metadata::new(120., 8));
+        assert!(matches!(
+            PlayerView::new(&bytes[..bytes.len() - 1]),
+            Err(Error::ChecksumMismatch { .. })
+        ));
+        assert!(matches!(
+            PlayerView::new(b"hello, world"),
+            Err(Error::NotATrackFile)
+        ));
+
+        // The second key of camera:x has the same row as the first
+        let second_row = HEADER_LEN + 4 + 4 + 8 + 4 + KEY_LEN;
+        bytes[second_row..second_row + 4].copy_from_slice(&0u32.to_le_bytes());
+        fix_checksum(&mut bytes);
+        assert!(matches!(
+            PlayerView::new(&bytes),
+            Err(Error::Corrupt("keys aren't sorted by row"))
+        ));
+        assert!(matches!(
+            from_bytes(&bytes),
+            Err(file::Error::View(Error::Corrupt(
+                "keys aren't sorted by row"
+            )))
+        ));
+
+        let mut bytes = file::to_bytes(&sample_project(), Metadata::new(120., 8));
+        bytes.push(0);
+        let len = (bytes.len() - HEADER_LEN) as u32;
+        bytes[20..24].copy_from_slice(&len.to_le_bytes());
+        fix_checksum(&mut bytes);
+        assert!(matches!(
+            PlayerView::new(&bytes),
+            Err(Error::Corrupt("trailing data after the tracks"))
+        ));
+    }
+}
@@ -0,0 +1,323 @@
+//! Reading and writing GNU Rocket editor projects, `.rocket` XML files.
+//!
+//! Requires the `xml` feature.
+//! The editor saves the tracks of a project as `<track name="...">` elements containing
+//! `<key row="..." value="..." interpolation="..."/>` elements.
+//! Editor-specific elements and attributes, e.g. colors and bookmarks, are ignored when reading.
+//!
+//! # Usage
+//!
+//! Play an editor project without a live session:
+//!
+//! ```rust,no_run
+//! # use rust_rocket::{xml, RocketPlayer};
+//! # use std::{fs::File, io::BufReader};
+//! let file = File::open("demo.rocket").expect("Failed to open demo.rocket");
+//! let tracks = xml::read(BufReader::new(file)).expect("Failed to read demo.rocket");
+//! let player = RocketPlayer::new(tracks);
+//! ```
+//!
+//! Turn tracks from [`RocketClient::save_tracks`](crate::RocketClient::save_tracks) back into a project that
+//! the editor can open:
+//!
+//! ```rust,no_run
+//! # use rust_rocket::{file, xml};
+//! # use std::fs::File;
+//! let file = File::open("tracks.bin").expect("Failed to open tracks.bin");
+//! let tracks = file::read(file).expect("Failed to read tracks.bin").into_tracks();
+//! let file = File::create("tracks.rocket").expect("Failed to create tracks.rocket");
+//! xml::write(file, &tracks).expect("Failed to write tracks.rocket");
+//! ```
+//!
+//! See also `examples/export.rs`.
+
+use crate::interpolation::Interpolation;
+use crate::track::{Key, Track};
+use crate::Tracks;
+
+use quick_xml::events::{BytesDecl, BytesStart, Event};
+use quick_xml::{Reader, Writer};
+use std::io::{self, BufRead, Write};
+use thiserror::Error;
+
+/// The number of rows in a new editor project. Projects are written with at least this many rows.
+const DEFAULT_ROWS: u32 = 10000;
+
+/// The `Error` Type for reading projects.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The file isn't well-formed XML, or reading it failed
+    #[error("Failed to parse XML")]
+    Xml(#[from] quick_xml::Error),
+    /// An element is missing a required attribute
+    #[error("<{element}> has no {attribute} attribute")]
+    MissingAttribute {
+        /// The element name
+        element: &'static str,
+        /// The attribute name
+        attribute: &'static str,
+    },
+    /// An attribute value can't be parsed
+    #[error("Invalid {attribute} attribute {value:?}")]
+    InvalidAttribute {
+        /// The attribute name
+        attribute: &'static str,
+        /// The attribute value
+        value: String,
+    },
+}
+
+/// Read tracks from a `.rocket` project.
+///
+/// `<track>` elements are accepted under any root element, so both current (`<rocket><tracks>`)
+/// and old (`<tracks>`) projects can be read. `<key>` elements outside of `<track>` are ignored.
+///
+/// # Errors
+///
+/// [`Error::Xml`] if the file isn't well-formed or can't be read,
+/// or another [`Error`](enum@Error) if a track or a key is missing attributes or has invalid ones.
+///
+/// # Examples
+///
+/// ```rust
+/// # use rust_rocket::xml;
+/// let project = r#"
+///     <rocket rows="10000">
+///         <tracks>
+///             <track name="camera:x">
+///                 <key row="8" value="1.5" interpolation="1"/>
+///             </track>
+///         </tracks>
+///     </rocket>"#;
+/// let tracks = xml::read(project.as_bytes())?;
+/// assert_eq!(tracks[0].get_name(), "camera:x");
+/// assert_eq!(tracks[0].get_value(8.), 1.5);
+/// # Ok::<(), xml::Error>(())
+/// ```
+pub fn read<R: BufRead>(reader: R) -> Result<Tracks, Error> {
+    let mut reader = Reader::from_reader(reader);
+    let mut buf = Vec::new();
+    let mut tracks = Vec::new();
+    let mut in_track = false;
+
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(e) if e.name().as_ref() == b"track" => {
+                tracks.push(Track::new(get_attribute(&e, "track", "name")?));
+                in_track = true;
+            }
+            Event::Empty(e) if e.name().as_ref() == b"track" => {
+                tracks.push(Track::new(get_attribute(&e, "track", "name")?));
+            }
+            Event::End(e) if e.name().as_ref() == b"track" => in_track = false,
+            Event::Start(e) | Event::Empty(e) if in_track && e.name().as_ref() == b"key" => {
+                let key = parse_key(&e)?;
+                if let Some(track) = tracks.last_mut() {
+                    track.set_key(key);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    Ok(tracks)
+}
+
+/// Write tracks as a `.rocket` project, which the editor can open.
+///
+/// # Errors
+///
+/// Any error from writing to `writer`.
+pub fn write<W: Write>(writer: W, tracks: &[Track]) -> io::Result<()> {
+    let rows = tracks
+        .iter()
+        .filter_map(|track| track.keys().last())
+        .map(|key| key.get_row().saturating_add(1))
+        .fold(DEFAULT_ROWS, u32::max);
+
+    let mut writer = Writer::new_with_indent(writer, b' ', 2);
+    writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("utf-8"), None)))?;
+    let rows = rows.to_string();
+    writer
+        .create_element("rocket")
+        .with_attributes([
+            ("rows", rows.as_str()),
+            ("startRow", "0"),
+            ("endRow", &rows),
+        ])
+        .write_inner_content(|writer| {
+            writer
+                .create_element("tracks")
+                .write_inner_content(|writer| {
+                    for track in tracks {
+                        write_track(writer, track)?;
+                    }
+                    Ok(())
+                })?;
+            Ok(())
+        })?;
+    writer.get_mut().write_all(b"\n")
+}
+
+/// Write tracks as a `.rocket` project into a `String`, see [`write`](fn@write).
+pub fn to_string(tracks: &[Track]) -> String {
+    let mut buf = Vec::new();
+    // Writing to a Vec can't fail
+    write(&mut buf, tracks).unwrap();
+    // The tracks are UTF-8 and the writer doesn't split characters
+    String::from_utf8(buf).unwrap()
+}
+
+fn write_track<W: Write>(writer: &mut Writer<W>, track: &Track) -> io::Result<()> {
+    let element = writer
+        .create_element("track")
+        .with_attribute(("name", track.get_name()));
+    if track.keys().is_empty() {
+        element.write_empty()?;
+        return Ok(());
+    }
+    element.write_inner_content(|writer| {
+        for key in track.keys() {
+            let row = key.get_row().to_string();
+            let value = key.get_value().to_string();
+            let interpolation = (key.get_interpolation() as u8).to_string();
+            writer
+                .create_element("key")
+                .with_attributes([
+                    ("row", row.as_str()),
+                    ("value", value.as_str()),
+                    ("interpolation", interpolation.as_str()),
+                ])
+                .write_empty()?;
+        }
+        Ok(())
+    })?;
+    Ok(())
+}
+
+fn parse_key(element: &BytesStart) -> Result<Key, Error> {
+    let row = get_attribute(element, "key", "row")?;
+    let row = row.trim().parse().map_err(|_| Error::InvalidAttribute {
+        attribute: "row",
+        value: row,
+    })?;
+
+    let value = get_attribute(element, "key", "value")?;
+    let value = value.trim().parse().map_err(|_| Error::InvalidAttribute {
+        attribute: "value",
+        value,
+    })?;
+
+    let interpolation = get_attribute(element, "key", "interpolation")?;
+    let interpolation = interpolation
+        .trim()
+        .parse()
+        .ok()
+        .and_then(Interpolation::from_u8)
+        .ok_or(Error::InvalidAttribute {
+            attribute: "interpolation",
+            value: interpolation,
+        })?;
+
+    Ok(Key::new(row, value, interpolation))
+}
+
+fn get_attribute(
+    element: &BytesStart,
+    element_name: &'static str,
+    name: &'static str,
+) -> Result<String, Error> {
+    for attribute in element.attributes() {
+        let attribute = attribute.map_err(quick_xml::Error::from)?;
+        if attribute.key.as_ref() == name.as_bytes() {
+            return Ok(attribute.unescape_value()?.into_owned());
+        }
+    }
+    Err(Error::MissingAttribute {
+        element: element_name,
+        attribute: name,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_tracks() -> Tracks {
+        let mut a = Track::new("camera:x & <y>");
+        a.set_key(Key::new(0, 1.0, Interpolation::Step));
+        a.set_key(Key::new(8, -0.1, Interpolation::Linear));
+        a.set_key(Key::new(20000, 3.25, Interpolation::Smooth));
+        let mut b = Track::new("\"quoted\"");
+        b.set_key(Key::new(4, 1e-7, Interpolation::Ramp));
+        vec![a, b, Track::new("empty")]
+    }
+
+    #[test]
+    fn roundtrip() {
+        let tracks = test_tracks();
+        let project = to_string(&tracks);
+        assert!(project.contains(r#"<rocket rows="20001" startRow="0" endRow="20001">"#));
+        assert_eq!(read(project.as_bytes()).unwrap(), tracks);
+    }
+
+    #[test]
+    fn editor_project() {
+        let project = r#"<?xml version="1.0" encoding="utf-8"?>
+<rocket rows="10000" startRow="0" endRow="10000" highlightRowStep="8">
+<tracks>
+<track name="camera:x" folded="0" muteKeyCount="0" color="ffb27474">
+<key row="0" value="0.000000" interpolation="0"/>
+<key row="16" value="2.500000" interpolation="2"/>
+</track>
+<track name="fx:mute" folded="1" muteKeyCount="0" color="ff8080ff"/>
+</tracks>
+<bookmarks>
+<bookmark row="32"/>
+</bookmarks>
+</rocket>
+"#;
+        let tracks = read(project.as_bytes()).unwrap();
+        assert_eq!(tracks.len(), 2);
+        assert_eq!(
+            tracks[0].keys(),
+            [
+                Key::new(0, 0.0, Interpolation::Step),
+                Key::new(16, 2.5, Interpolation::Smooth)
+            ]
+        );
+        assert_eq!(tracks[1].get_name(), "fx:mute");
+        assert!(tracks[1].keys().is_empty());
+
+        // Old projects have no <rocket> element
+        let old = r#"<tracks><track name="a"><key row="1" value="1" interpolation="1"/></track></tracks>"#;
+        assert_eq!(read(old.as_bytes()).unwrap()[0].keys().len(), 1);
+    }
+
+    #[test]
+    fn invalid_projects() {
+        let missing =
+            r#"<tracks><track><key row="1" value="1" interpolation="1"/></track></tracks>"#;
+        assert!(matches!(
+            read(missing.as_bytes()),
+            Err(Error::MissingAttribute {
+                element: "track",
+                attribute: "name"
+            })
+        ));
+
+        let invalid = r#"<tracks><track name="a"><key row="1" value="1" interpolation="7"/></track></tracks>"#;
+        assert!(matches!(
+            read(invalid.as_bytes()),
+            Err(Error::InvalidAttribute {
+                attribute: "interpolation",
+                ..
+            })
+        ));
+
+        let malformed = r#"<tracks><track name="a"></tracks>"#;
+        assert!(matches!(read(malformed.as_bytes()), Err(Error::Xml(_))));
+    }
+}